@@ -0,0 +1,354 @@
+use std::collections::VecDeque;
+
+use bevy::math::bounding::Aabb2d;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameState, Player, Position, Velocity, Wall};
+use crate::collision::{FlapAnimation, FRAME_SIZE, PLAYER_SPRITE_SCALE, PlayerMasks, PlayerShape};
+use crate::difficulty::{Difficulty, DifficultySetting};
+use crate::generator::WallPattern;
+use crate::input::any_input_just_pressed;
+use crate::patterns::PatternSetting;
+use crate::persistence::Storage;
+use crate::power_ups::ActivePowerUps;
+use crate::replay::{autopiloting, ReplayMode};
+use crate::simulation::{Flap, hits_ground, Playfield, predict_wall_bounds, Seed, SimulationSet, TickRate, WallMotion};
+use crate::tuning::GameTuning;
+
+// The autopilot flaps on whichever ticks keep it alive, found by simulating the player and the walls a
+// little way ahead, tick by tick, with and without a flap. It's used for demo runs while the title screen
+// sits idle, and headlessly to check that courses can be survived:
+//   cargo run -- --headless 100000 --autopilot
+
+/// Seconds of simulated time the autopilot looks ahead.
+const HORIZON: f32 = 1.5;
+
+/// How many simulated ticks the autopilot may search through before each decision.
+const SEARCH_BUDGET: usize = 20_000;
+
+/// How far the autopilot keeps from walls, beyond what would collide.
+const CLEARANCE: f32 = 1.0;
+
+/// Seconds the title screen has to sit idle before a demo run starts.
+const ATTRACT_DELAY: f32 = 10.0;
+
+pub fn plugin(app: &mut App) {
+    simulation_plugin(app);
+
+    app
+        .insert_resource(AttractMode::default())
+        .insert_resource(IdleTimer(Timer::from_seconds(ATTRACT_DELAY, TimerMode::Once)))
+        .add_event::<ToggleAttractMode>()
+        .add_systems(Startup, load_attract_mode)
+        .add_systems(Update, toggle_attract_mode.run_if(on_event::<ToggleAttractMode>()))
+        .add_systems(OnEnter(GameState::PreGame), (reset_idle_timer, stop_autopilot.run_if(autopiloting)))
+        .add_systems(Update, (reset_idle_timer.run_if(any_input_just_pressed), start_demo).chain().run_if(in_state(GameState::PreGame).and_then(attract_mode_on)))
+        .add_systems(OnEnter(GameState::InProgress), spawn_demo_label.run_if(autopiloting))
+        .add_systems(OnExit(GameState::InProgress), despawn_demo_label)
+        .add_systems(Update, end_demo.run_if(in_state(GameState::InProgress).and_then(autopiloting).and_then(any_input_just_pressed.or_else(game_over_pending))));
+}
+
+/// Only what's needed for the autopilot to fly the player.
+pub fn simulation_plugin(app: &mut App) {
+    app
+        .init_resource::<ReplayMode>()
+        .init_resource::<FlightPlan>()
+        .add_systems(OnEnter(GameState::InProgress), clear_flight_plan)
+        .add_systems(FixedUpdate, steer.in_set(SimulationSet::Input).run_if(autopiloting));
+}
+
+/// Whether to flap on each of the ticks ahead, as last worked out.
+#[derive(Resource, Default)]
+struct FlightPlan(VecDeque<bool>);
+
+fn clear_flight_plan(mut plan: ResMut<FlightPlan>) {
+    plan.0.clear();
+}
+
+/// Everything about the player that changes from one tick to the next.
+#[derive(Clone, Copy)]
+struct Flight {
+    y: f32,
+    vy: f32,
+    animation: FlapAnimation,
+    /// Ticks since the flight last flapped while looking ahead, which is enough to know its speed and
+    /// animation exactly.
+    since_flap: Option<u32>,
+}
+
+/// The walls, as they'll be, and the player, as it is, for simulating the ticks ahead.
+struct Lookahead<'a> {
+    x: f32,
+    dt: f32,
+    steps: usize,
+    tuning: &'a GameTuning,
+    playfield: &'a Playfield,
+    shape_scale: f32,
+    masks: &'a PlayerMasks,
+    /// The bounding box of each wall which may reach the player, for each tick ahead.
+    walls: Vec<Vec<Aabb2d>>,
+}
+
+impl Lookahead<'_> {
+    /// The flight one tick later, the same way the simulation steps it.
+    fn advance(&self, flight: Flight, flap: bool) -> Flight {
+        let mut next = flight;
+
+        if flap {
+            next.vy = self.tuning.impulse;
+            next.animation.0 = 0.0;
+            next.since_flap = Some(0);
+        } else {
+            next.animation.0 += self.dt;
+            next.since_flap = flight.since_flap.map(|ticks| ticks + 1);
+        }
+
+        next.vy += self.tuning.gravity * self.dt;
+        next.y += next.vy * self.dt;
+        next
+    }
+
+    fn can_flap(&self, flight: Flight) -> bool {
+        // a flap above the playfield does nothing, so there's no sense trying one
+        flight.y < self.playfield.height / 2.0
+    }
+
+    /// Whether the flight crashes `step` ticks from now.
+    fn crashes(&self, flight: Flight, step: usize) -> bool {
+        if hits_ground(flight.y, self.tuning.player_radius * self.shape_scale, self.playfield) {
+            return true;
+        }
+
+        let shape = PlayerShape::new(self.tuning, self.masks, flight.animation, self.shape_scale);
+        let position = Vec2::new(self.x, flight.y);
+        self.walls[step - 1].iter().any(|bounds| shape.hits(position, bounds))
+    }
+
+    /// Finds flaps which keep the flight alive from `step` ticks from now to the horizon, trying not to
+    /// flap first. They're pushed onto `plan` last first.
+    fn search(&self, flight: Flight, step: usize, visited: &mut HashSet<(usize, i32, u32)>, plan: &mut Vec<bool>) -> bool {
+        if step == self.steps {
+            return true;
+        }
+
+        for flap in [false, true] {
+            if visited.len() >= SEARCH_BUDGET || flap && !self.can_flap(flight) {
+                continue;
+            }
+
+            let next = self.advance(flight, flap);
+            if self.crashes(next, step + 1) {
+                continue;
+            }
+
+            // flights which flapped equally long ago only differ in height, so those within a few units of
+            // one which was already searched are taken to end the same way
+            let key = (step + 1, (next.y / 4.0).round() as i32, next.since_flap.unwrap_or(u32::MAX));
+            if visited.insert(key) && self.search(next, step + 1, visited, plan) {
+                plan.push(flap);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Flaps which keep the flight alive until the horizon, starting from this tick, if there are any.
+    fn plan(&self, flight: Flight) -> Option<VecDeque<bool>> {
+        let mut plan = Vec::new();
+        let found = self.search(flight, 0, &mut HashSet::new(), &mut plan);
+        found.then(|| plan.into_iter().rev().collect())
+    }
+
+    /// Whether following the plan keeps the flight alive for as long as the plan goes on, which an
+    /// exhausted plan doesn't.
+    fn still_works(&self, flight: Flight, plan: &VecDeque<bool>) -> bool {
+        let mut flight = flight;
+
+        !plan.is_empty() && plan.iter().take(self.steps).enumerate().all(|(step, flap)| {
+            if *flap && !self.can_flap(flight) {
+                return false;
+            }
+            flight = self.advance(flight, *flap);
+            !self.crashes(flight, step + 1)
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn steer(
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    playfield: Res<Playfield>,
+    masks: Res<PlayerMasks>,
+    animation: Res<FlapAnimation>,
+    power_ups: Res<ActivePowerUps>,
+    player: Query<(&Position, &Velocity), With<Player>>,
+    walls: Query<(&Wall, Option<&WallMotion>)>,
+    mut plan: ResMut<FlightPlan>,
+    mut writer: EventWriter<Flap>,
+) {
+    let tuning = tuning.with_difficulty(*difficulty);
+    let (position, velocity) = player.single();
+    let dt = time.delta_seconds();
+    let steps = (HORIZON / dt).ceil() as usize;
+
+    // only walls which come within reach of the player before the horizon
+    let reach = FRAME_SIZE as f32 * PLAYER_SPRITE_SCALE;
+    let nearby: Vec<_> = walls.iter()
+        .filter(|(wall, _)| power_ups.shielded_hole != Some(wall.hole))
+        .filter(|(wall, _)| wall.bounding_box.max.x > position.0.x - reach && wall.bounding_box.min.x + tuning.wall_speed * HORIZON < position.0.x + reach)
+        .collect();
+
+    let predict = |step: usize, wall: &Wall, motion: Option<&WallMotion>| {
+        let bounds = predict_wall_bounds(wall, motion, step as f32 * dt, &tuning);
+        Aabb2d { min: bounds.min - CLEARANCE, max: bounds.max + CLEARANCE }
+    };
+
+    let lookahead = Lookahead {
+        x: position.0.x,
+        dt,
+        steps,
+        tuning: &tuning,
+        playfield: &playfield,
+        shape_scale: power_ups.player_scale(&tuning),
+        masks: &masks,
+        walls: (1..=steps).map(|step| nearby.iter().map(|(wall, motion)| predict(step, wall, *motion)).collect()).collect(),
+    };
+
+    let flight = Flight { y: position.0.y, vy: velocity.0.y, animation: *animation, since_flap: None };
+
+    // the last plan is kept while no new one can be found, as it may only have been found with searching
+    // left to spare
+    if let Some(found) = lookahead.plan(flight) {
+        plan.0 = found;
+    } else if !lookahead.still_works(flight, &plan.0) {
+        // with no way through, stay in the air for as long as possible
+        plan.0 = VecDeque::from([flight.vy < 0.0]);
+    }
+
+    if plan.0.pop_front().unwrap_or(false) {
+        writer.send(Flap);
+    }
+}
+
+/// Whether demo runs start by themselves when the title screen is left idle.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AttractMode(pub bool);
+
+impl Default for AttractMode {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+fn attract_mode_on(attract: Res<AttractMode>) -> bool {
+    attract.0
+}
+
+fn load_attract_mode(
+    mut attract: ResMut<AttractMode>,
+    storage: Storage,
+) {
+    if let Some(stored) = storage.get::<AttractMode>("attract mode") {
+        *attract = stored;
+    }
+}
+
+#[derive(Event, Default)]
+pub struct ToggleAttractMode;
+
+fn toggle_attract_mode(
+    mut attract: ResMut<AttractMode>,
+    mut storage: Storage,
+) {
+    attract.0 = !attract.0;
+    storage.set("attract mode", &*attract);
+}
+
+/// How long the title screen has sat idle, in real time, since virtual time is paused there.
+#[derive(Resource)]
+struct IdleTimer(Timer);
+
+fn reset_idle_timer(mut idle: ResMut<IdleTimer>) {
+    idle.0.reset();
+}
+
+// a demo is played like a run on the player's chosen course, but on a fresh seed, and never recorded
+#[allow(clippy::too_many_arguments)]
+fn start_demo(
+    time: Res<Time<Real>>,
+    mut idle: ResMut<IdleTimer>,
+    difficulty_setting: Res<DifficultySetting>,
+    pattern_setting: Res<PatternSetting>,
+    tick_rate: Res<TickRate>,
+    mut difficulty: ResMut<Difficulty>,
+    mut pattern: ResMut<WallPattern>,
+    mut seed: ResMut<Seed>,
+    mut mode: ResMut<ReplayMode>,
+    mut fixed: ResMut<Time<Fixed>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !idle.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    *difficulty = difficulty_setting.0;
+    *pattern = pattern_setting.0.clone();
+    seed.0 = random();
+    fixed.set_timestep_hz(tick_rate.0);
+    *mode = ReplayMode::Autopilot;
+    next_state.set(GameState::InProgress);
+}
+
+fn game_over_pending(next_state: Res<NextState<GameState>>) -> bool {
+    matches!(next_state.0, Some(GameState::GameOver))
+}
+
+// a demo goes back to the title screen instead of ending in game over
+fn end_demo(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::PreGame);
+}
+
+fn stop_autopilot(mut mode: ResMut<ReplayMode>) {
+    *mode = ReplayMode::Recording;
+}
+
+#[derive(Component)]
+struct DemoLabel;
+
+fn spawn_demo_label(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "Demo: press anything to stop",
+                TextStyle {
+                    color: Color::BLACK,
+                    font_size: 40.0,
+                    ..default()
+                }
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                right: Val::Px(20.0),
+                ..default()
+            },
+            ..default()
+        },
+        DemoLabel
+    ));
+}
+
+fn despawn_demo_label(
+    mut commands: Commands,
+    labels: Query<Entity, With<DemoLabel>>,
+) {
+    for entity in &labels {
+        commands.entity(entity).despawn_recursive();
+    }
+}
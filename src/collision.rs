@@ -0,0 +1,347 @@
+use bevy::math::bounding::{Aabb2d, Bounded2d, BoundingVolume, IntersectsVolume};
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::simulation::{Crashes, Flap, SimulationSet};
+use crate::tuning::GameTuning;
+
+// The player collides with walls using the solid pixels of whichever frame of bird.png is on screen.
+// The frame is worked out from the time since the last flap, in simulated time, so the simulation and the
+// sprite always agree on it and runs stay deterministic. For the same reason, no tick is simulated until
+// bird.png has either loaded or failed to, so a run never starts with one shape and carries on with another.
+
+pub const BIRD_PATH: &str = "bird.png";
+
+/// bird.png is a single row of square frames.
+pub const FRAME_SIZE: u32 = 64;
+pub const FRAME_COUNT: u32 = 8;
+
+/// The bird is drawn this many times larger than its frames, before any power-ups.
+pub const PLAYER_SPRITE_SCALE: f32 = 3.0;
+
+/// The frames played after each flap, after which the first frame is shown until the next flap.
+const FLAP_FRAMES: [usize; 15] = [0, 1, 2, 3, 4, 5, 6, 7, 6, 5, 4, 3, 2, 1, 0];
+const FLAP_FPS: f32 = 24.0;
+
+/// Pixels with less alpha than this are see-through.
+const SOLID_ALPHA: u8 = 128;
+
+pub fn plugin(app: &mut App) {
+    simulation_plugin(app);
+
+    app
+        .add_systems(Startup, load_bird)
+        .add_systems(Update, build_masks.run_if(on_event::<AssetEvent<Image>>()))
+        .add_systems(Update, notice_failed_load.run_if(masks_loading));
+}
+
+/// Only what's needed to collide with walls; the masks themselves come from the window's asset server,
+/// or from disk when headless.
+pub fn simulation_plugin(app: &mut App) {
+    app
+        .init_resource::<PlayerMasks>()
+        .init_resource::<FlapAnimation>()
+        .add_systems(OnEnter(GameState::InProgress), reset_flap_animation)
+        .add_systems(FixedUpdate, animate_flap.before(Crashes).in_set(SimulationSet::Step));
+}
+
+/// How the player collides with walls.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PlayerCollision {
+    /// The solid pixels of the frame being shown, or the circles if bird.png can't be loaded.
+    #[default]
+    Mask,
+    /// The player's head and body circles, which are also what's used for pickups and the ground.
+    Circles,
+}
+
+/// Seconds of simulated time since the player last flapped.
+#[derive(Resource, Clone, Copy)]
+pub struct FlapAnimation(pub f32);
+
+impl Default for FlapAnimation {
+    fn default() -> Self {
+        // at rest, rather than part way through a flap
+        Self(f32::INFINITY)
+    }
+}
+
+impl FlapAnimation {
+    /// The index of the frame of bird.png being shown.
+    pub fn frame(&self) -> usize {
+        let played = (self.0 * FLAP_FPS) as usize;
+        FLAP_FRAMES.get(played).copied().unwrap_or(0)
+    }
+}
+
+fn reset_flap_animation(mut animation: ResMut<FlapAnimation>) {
+    *animation = FlapAnimation::default();
+}
+
+fn animate_flap(
+    mut reader: EventReader<Flap>,
+    time: Res<Time>,
+    mut animation: ResMut<FlapAnimation>,
+) {
+    if reader.read().count() > 0 {
+        animation.0 = 0.0;
+    } else {
+        animation.0 += time.delta_seconds();
+    }
+}
+
+/// Which pixels of one frame of bird.png are solid, row by row from the top.
+pub struct FrameMask {
+    solid: Vec<bool>,
+    /// The top left and bottom right solid pixels, if any are.
+    corners: Option<(Vec2, Vec2)>,
+}
+
+impl FrameMask {
+    fn new(solid: Vec<bool>) -> Self {
+        let size = FRAME_SIZE as usize;
+        let mut pixels = solid.iter().enumerate().filter(|(_, solid)| **solid).map(|(i, _)| Vec2::new((i % size) as f32, (i / size) as f32));
+        let corners = pixels.next().map(|first| pixels.fold((first, first), |(min, max), pixel| (min.min(pixel), max.max(pixel))));
+
+        Self { solid, corners }
+    }
+
+    /// Whether any solid pixel overlaps the rectangle, with the frame centered on `center` and each of its
+    /// pixels `pixel_size` world units across.
+    pub fn hits(&self, center: Vec2, pixel_size: f32, rectangle: &Aabb2d) -> bool {
+        let half = FRAME_SIZE as f32 / 2.0;
+        let size = FRAME_SIZE as f32;
+
+        // the rectangle in pixels, with y increasing downward like the image's rows
+        let min_x = ((rectangle.min.x - center.x) / pixel_size + half).floor().clamp(0.0, size) as usize;
+        let max_x = ((rectangle.max.x - center.x) / pixel_size + half).ceil().clamp(0.0, size) as usize;
+        let min_y = ((center.y - rectangle.max.y) / pixel_size + half).floor().clamp(0.0, size) as usize;
+        let max_y = ((center.y - rectangle.min.y) / pixel_size + half).ceil().clamp(0.0, size) as usize;
+
+        (min_y..max_y).any(|y| (min_x..max_x).any(|x| self.solid[y * FRAME_SIZE as usize + x]))
+    }
+
+    /// The box around the solid pixels, relative to the frame's center, with each pixel `pixel_size` world
+    /// units across.
+    pub fn bounds(&self, pixel_size: f32) -> Aabb2d {
+        let half = FRAME_SIZE as f32 / 2.0;
+
+        let Some((min, max)) = self.corners else {
+            return Aabb2d::new(Vec2::ZERO, Vec2::ZERO);
+        };
+
+        // rows count down from the top, and each pixel extends a whole pixel right of and below its corner
+        Aabb2d {
+            min: Vec2::new(min.x - half, half - max.y - 1.0) * pixel_size,
+            max: Vec2::new(max.x + 1.0 - half, half - min.y) * pixel_size,
+        }
+    }
+
+    /// Whether the pixel `x` across and `y` down from the top left of the frame is solid.
+    #[cfg(feature = "debug")]
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        let size = FRAME_SIZE as i32;
+        (0..size).contains(&x) && (0..size).contains(&y) && self.solid[(y * size + x) as usize]
+    }
+}
+
+/// Everything needed to tell whether the player overlaps a rectangle, wherever the player is.
+pub struct PlayerShape<'a> {
+    mask: Option<&'a FrameMask>,
+    pixel_size: f32,
+    radius: f32,
+    body_offset: Vec2,
+    head_offset: Vec2,
+}
+
+impl<'a> PlayerShape<'a> {
+    /// The player's shape at this point in its flap animation, with power-ups scaling it by `scale`.
+    pub fn new(tuning: &GameTuning, masks: &'a PlayerMasks, animation: FlapAnimation, scale: f32) -> Self {
+        let mask = match tuning.player_collision {
+            PlayerCollision::Mask => masks.frame(animation.frame()),
+            PlayerCollision::Circles => None,
+        };
+
+        Self {
+            mask,
+            pixel_size: PLAYER_SPRITE_SCALE * scale,
+            radius: tuning.player_radius * scale,
+            body_offset: tuning.body_offset * scale,
+            head_offset: tuning.head_offset * scale,
+        }
+    }
+
+    /// The box around everything the player collides with, relative to its position.
+    pub fn bounds(&self) -> Aabb2d {
+        match self.mask {
+            Some(mask) => mask.bounds(self.pixel_size),
+            None => {
+                let circle = Circle::new(self.radius);
+                circle.aabb_2d(self.body_offset, 0.0).merge(&circle.aabb_2d(self.head_offset, 0.0))
+            }
+        }
+    }
+
+    pub fn hits(&self, position: Vec2, rectangle: &Aabb2d) -> bool {
+        match self.mask {
+            Some(mask) => mask.hits(position, self.pixel_size, rectangle),
+            None => {
+                let circle = Circle::new(self.radius);
+                circle.bounding_circle(position + self.body_offset, 0.0).intersects(rectangle)
+                    || circle.bounding_circle(position + self.head_offset, 0.0).intersects(rectangle)
+            }
+        }
+    }
+}
+
+/// A mask for each frame of bird.png, once it has loaded.
+#[derive(Resource, Default)]
+pub enum PlayerMasks {
+    #[default]
+    Loading,
+    Loaded(Vec<FrameMask>),
+    /// bird.png couldn't be loaded, or couldn't be made into masks, so the circles are used instead.
+    Unavailable,
+}
+
+/// Whether ticks can be simulated, which they can't while it's still unknown what the player collides with.
+pub fn collision_ready(tuning: Res<GameTuning>, masks: Res<PlayerMasks>) -> bool {
+    tuning.player_collision == PlayerCollision::Circles || !matches!(*masks, PlayerMasks::Loading)
+}
+
+fn masks_loading(masks: Res<PlayerMasks>) -> bool {
+    matches!(*masks, PlayerMasks::Loading)
+}
+
+impl PlayerMasks {
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let format = image.texture_descriptor.format;
+        if !matches!(format, TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm) {
+            warn!("can't build collision masks from {} in {:?}", BIRD_PATH, format);
+            return None;
+        }
+
+        if image.width() < FRAME_SIZE * FRAME_COUNT || image.height() < FRAME_SIZE {
+            warn!("{} is too small to hold {} frames", BIRD_PATH, FRAME_COUNT);
+            return None;
+        }
+
+        let alpha = |x: u32, y: u32| image.data[((y * image.width() + x) * 4 + 3) as usize];
+
+        let masks = (0..FRAME_COUNT).map(|frame| FrameMask::new(
+            (0..FRAME_SIZE)
+                .flat_map(|y| (0..FRAME_SIZE).map(move |x| (frame * FRAME_SIZE + x, y)))
+                .map(|(x, y)| alpha(x, y) >= SOLID_ALPHA)
+                .collect(),
+        )).collect();
+
+        Some(Self::Loaded(masks))
+    }
+
+    pub fn frame(&self, index: usize) -> Option<&FrameMask> {
+        match self {
+            PlayerMasks::Loaded(masks) => masks.get(index),
+            PlayerMasks::Loading | PlayerMasks::Unavailable => None,
+        }
+    }
+}
+
+/// Keeps bird.png loaded, so that the masks are rebuilt when it changes.
+#[derive(Resource)]
+struct BirdHandle(Handle<Image>);
+
+fn load_bird(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(BirdHandle(asset_server.load(BIRD_PATH)));
+}
+
+fn build_masks(
+    mut events: EventReader<AssetEvent<Image>>,
+    handle: Res<BirdHandle>,
+    images: Res<Assets<Image>>,
+    mut masks: ResMut<PlayerMasks>,
+) {
+    for event in events.read() {
+        if event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0) {
+            match images.get(&handle.0).and_then(PlayerMasks::from_image) {
+                Some(built) => *masks = built,
+                // a change which breaks the masks keeps the ones already built
+                None if matches!(*masks, PlayerMasks::Loading) => *masks = PlayerMasks::Unavailable,
+                None => {}
+            }
+        }
+    }
+}
+
+fn notice_failed_load(
+    handle: Res<BirdHandle>,
+    asset_server: Res<AssetServer>,
+    mut masks: ResMut<PlayerMasks>,
+) {
+    if asset_server.load_state(&handle.0) == LoadState::Failed {
+        warn!("failed to load {}, colliding with circles", BIRD_PATH);
+        *masks = PlayerMasks::Unavailable;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::bounding::Aabb2d;
+    use bevy::prelude::*;
+
+    use super::{FRAME_SIZE, FrameMask};
+
+    const PIXEL_SIZE: f32 = 2.0;
+
+    /// A frame whose only solid pixel is 40 across and 10 down from the top left, so up and to the right of
+    /// the center. Each pixel is 2 units across, so it covers x 16 to 18 and y 42 to 44 around the center.
+    fn mask() -> FrameMask {
+        let mut solid = vec![false; (FRAME_SIZE * FRAME_SIZE) as usize];
+        solid[(10 * FRAME_SIZE + 40) as usize] = true;
+        FrameMask::new(solid)
+    }
+
+    fn rectangle(min: (f32, f32), max: (f32, f32)) -> Aabb2d {
+        Aabb2d { min: Vec2::new(min.0, min.1), max: Vec2::new(max.0, max.1) }
+    }
+
+    #[test]
+    fn a_rectangle_over_a_solid_pixel_hits() {
+        assert!(mask().hits(Vec2::ZERO, PIXEL_SIZE, &rectangle((16.5, 42.5), (17.5, 43.5))));
+        // wherever the frame is
+        assert!(mask().hits(Vec2::new(100.0, -50.0), PIXEL_SIZE, &rectangle((116.5, -7.5), (117.5, -6.5))));
+    }
+
+    #[test]
+    fn a_rectangle_over_a_transparent_pixel_misses() {
+        // the same pixel flipped vertically, then horizontally
+        assert!(!mask().hits(Vec2::ZERO, PIXEL_SIZE, &rectangle((16.5, -43.5), (17.5, -42.5))));
+        assert!(!mask().hits(Vec2::ZERO, PIXEL_SIZE, &rectangle((-17.5, 42.5), (-16.5, 43.5))));
+        // and just beside it
+        assert!(!mask().hits(Vec2::ZERO, PIXEL_SIZE, &rectangle((18.5, 42.5), (19.5, 43.5))));
+    }
+
+    #[test]
+    fn rectangles_are_clipped_to_the_frame() {
+        // partly outside the frame, over the solid pixel
+        assert!(mask().hits(Vec2::ZERO, PIXEL_SIZE, &rectangle((16.5, 42.5), (1000.0, 1000.0))));
+        // partly outside the frame, over none
+        assert!(!mask().hits(Vec2::ZERO, PIXEL_SIZE, &rectangle((-1000.0, -1000.0), (10.0, 10.0))));
+        // entirely outside the frame
+        assert!(!mask().hits(Vec2::ZERO, PIXEL_SIZE, &rectangle((200.0, 200.0), (300.0, 300.0))));
+    }
+
+    #[test]
+    fn the_bounds_of_a_single_pixel_cover_just_that_pixel() {
+        let bounds = mask().bounds(PIXEL_SIZE);
+        assert_eq!((bounds.min, bounds.max), (Vec2::new(16.0, 42.0), Vec2::new(18.0, 44.0)));
+
+        let empty = FrameMask::new(vec![false; (FRAME_SIZE * FRAME_SIZE) as usize]).bounds(PIXEL_SIZE);
+        assert_eq!((empty.min, empty.max), (Vec2::ZERO, Vec2::ZERO));
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameMode, GameState, Score};
+use crate::persistence::Storage;
+use crate::replay::recording;
+use crate::scoring::ScoringMode;
+use crate::seed::Date;
+
+pub const DAILY_ATTEMPTS: u32 = 3;
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(DailyChallenge::default())
+        .add_systems(Startup, load_daily_challenge)
+        .add_systems(OnEnter(GameState::InProgress), start_attempt.run_if(in_daily_challenge.and_then(recording)))
+        .add_systems(OnEnter(GameState::GameOver), finish_attempt.run_if(in_daily_challenge.and_then(recording)));
+}
+
+pub fn in_daily_challenge(mode: Res<GameMode>) -> bool {
+    *mode == GameMode::DailyChallenge
+}
+
+/// Today's attempts at the daily challenge, stored separately from the all-time high score.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct DailyChallenge {
+    pub date: Date,
+    pub best: u64,
+    #[serde(default)]
+    pub best_walls: u64,
+    pub attempts: u32,
+}
+
+impl Default for DailyChallenge {
+    fn default() -> Self {
+        Self {
+            date: Date::today(),
+            best: 0,
+            best_walls: 0,
+            attempts: 0,
+        }
+    }
+}
+
+impl DailyChallenge {
+    /// Starts over if the stored attempts were made on a previous day.
+    pub fn refresh(&mut self) {
+        let today = Date::today();
+        if self.date != today {
+            *self = Self::default();
+        }
+    }
+
+    pub fn attempts_remaining(&self) -> u32 {
+        DAILY_ATTEMPTS.saturating_sub(self.attempts)
+    }
+
+    /// Today's best score by the given measure.
+    pub fn best(&self, scoring: ScoringMode) -> u64 {
+        match scoring {
+            ScoringMode::Time => self.best,
+            ScoringMode::Walls => self.best_walls,
+        }
+    }
+
+    fn record(&mut self, scoring: ScoringMode, score: u64) {
+        let best = match scoring {
+            ScoringMode::Time => &mut self.best,
+            ScoringMode::Walls => &mut self.best_walls,
+        };
+        *best = score.max(*best);
+    }
+}
+
+fn load_daily_challenge(
+    mut daily: ResMut<DailyChallenge>,
+    storage: Storage,
+) {
+    if let Some(stored) = storage.get::<DailyChallenge>("daily challenge") {
+        *daily = stored;
+    }
+    daily.refresh();
+}
+
+// an attempt counts as soon as it starts, even if it's then restarted or quit from the pause menu
+fn start_attempt(
+    mut daily: ResMut<DailyChallenge>,
+    scoring: Res<ScoringMode>,
+    mut score: ResMut<Score>,
+    mut storage: Storage,
+) {
+    daily.refresh();
+    daily.attempts += 1;
+    score.high = daily.best(*scoring);
+
+    storage.set("daily challenge", &*daily);
+}
+
+// only attempts which reach game over can set today's best, like runs on the leaderboard
+pub fn finish_attempt(
+    mut daily: ResMut<DailyChallenge>,
+    scoring: Res<ScoringMode>,
+    score: Res<Score>,
+    mut storage: Storage,
+) {
+    daily.record(*scoring, score.current);
+
+    storage.set("daily challenge", &*daily);
+}
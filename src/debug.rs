@@ -0,0 +1,209 @@
+use bevy::app::FixedMain;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+
+use crate::{Player, Position, Wall};
+use crate::collision::{FlapAnimation, FRAME_SIZE, PLAYER_SPRITE_SCALE, PlayerCollision, PlayerMasks};
+use crate::difficulty::Difficulty;
+use crate::pickups::Pickup;
+use crate::power_ups::ActivePowerUps;
+use crate::simulation::{Crashes, Playfield, PreviousHole, Seed, Tick};
+use crate::tuning::GameTuning;
+
+// A developer overlay, only compiled in with `cargo run --features debug`.
+//   F3  show or hide the overlay
+//   F4  toggle invincibility
+//   F5  stop or restart fixed ticks
+//   F6  advance one fixed tick while they're stopped
+
+pub fn plugin(app: &mut App) {
+    if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+        app.add_plugins(FrameTimeDiagnosticsPlugin);
+    }
+
+    app
+        .insert_resource(DebugSettings::default())
+        .configure_sets(FixedUpdate, Crashes.run_if(not(invincible)))
+        .add_systems(Startup, spawn_overlay)
+        .add_systems(Update, toggle_overlay.run_if(key_just_pressed(KeyCode::F3)))
+        .add_systems(Update, toggle_invincibility.run_if(key_just_pressed(KeyCode::F4)))
+        .add_systems(Update, toggle_stepping.run_if(key_just_pressed(KeyCode::F5)))
+        .add_systems(Update, step_one_tick.run_if(stepping.and_then(key_just_pressed(KeyCode::F6))))
+        .add_systems(Update, (show_overlay, draw_collision_shapes).run_if(overlay_visible));
+}
+
+#[derive(Resource, Default)]
+struct DebugSettings {
+    visible: bool,
+    invincible: bool,
+    /// Whether fixed ticks only run when stepped through one at a time.
+    stepping: bool,
+}
+
+fn key_just_pressed(key: KeyCode) -> impl FnMut(Res<ButtonInput<KeyCode>>) -> bool {
+    move |keys: Res<ButtonInput<KeyCode>>| keys.just_pressed(key)
+}
+
+fn overlay_visible(settings: Res<DebugSettings>) -> bool {
+    settings.visible
+}
+
+fn invincible(settings: Res<DebugSettings>) -> bool {
+    settings.invincible
+}
+
+fn stepping(settings: Res<DebugSettings>) -> bool {
+    settings.stepping
+}
+
+fn toggle_overlay(
+    mut settings: ResMut<DebugSettings>,
+    mut overlay: Query<&mut Visibility, With<DebugOverlay>>,
+) {
+    settings.visible = !settings.visible;
+
+    for mut visibility in &mut overlay {
+        *visibility = if settings.visible { Visibility::Visible } else { Visibility::Hidden };
+    }
+}
+
+fn toggle_invincibility(mut settings: ResMut<DebugSettings>) {
+    settings.invincible = !settings.invincible;
+}
+
+// fixed ticks are stopped by pausing virtual time, so nothing accumulates toward the next one
+fn toggle_stepping(
+    mut settings: ResMut<DebugSettings>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    settings.stepping = !settings.stepping;
+
+    if settings.stepping {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+}
+
+// runs FixedMain once, the same way the fixed timestep would
+fn step_one_tick(world: &mut World) {
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    world.run_schedule(FixedMain);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+#[derive(Component)]
+struct DebugOverlay;
+
+fn spawn_overlay(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    color: Color::RED,
+                    font_size: 24.0,
+                    ..default()
+                }
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                right: Val::Px(20.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        DebugOverlay
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    tick: Res<Tick>,
+    seed: Res<Seed>,
+    previous_hole: Res<PreviousHole>,
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    playfield: Res<Playfield>,
+    settings: Res<DebugSettings>,
+    entities: Query<Entity>,
+    walls: Query<(), With<Wall>>,
+    pickups: Query<(), With<Pickup>>,
+    mut overlay: Query<&mut Text, With<DebugOverlay>>,
+) {
+    let fps = diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS).and_then(|fps| fps.smoothed()).unwrap_or_default();
+
+    let tuning = tuning.with_difficulty(*difficulty);
+    let context = previous_hole.next_context(&tuning, &playfield);
+    let (min_size, max_size) = context.size_range();
+
+    let lines = [
+        format!("FPS: {:.0}", fps),
+        format!("Tick: {}", tick.0),
+        format!("Seed: {}", seed.0),
+        format!("Next hole: {}", previous_hole.index),
+        format!("Max drift: {:.1}", context.max_drift()),
+        format!("Hole size: {:.1} to {:.1}", min_size, max_size),
+        format!("Entities: {} ({} walls, {} pickups)", entities.iter().count(), walls.iter().count(), pickups.iter().count()),
+        format!("Invincible (F4): {}", settings.invincible),
+        format!("Stepping (F5, F6): {}", settings.stepping),
+    ];
+
+    for mut text in &mut overlay {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_collision_shapes(
+    mut gizmos: Gizmos,
+    tuning: Res<GameTuning>,
+    masks: Res<PlayerMasks>,
+    animation: Res<FlapAnimation>,
+    power_ups: Res<ActivePowerUps>,
+    players: Query<(&Position, &Player)>,
+    walls: Query<&Wall>,
+    pickups: Query<&Pickup>,
+) {
+    for (position, player) in &players {
+        let mask = match tuning.player_collision {
+            PlayerCollision::Mask => masks.frame(animation.frame()),
+            PlayerCollision::Circles => None,
+        };
+
+        match mask {
+            // outlines the solid pixels, drawing only those at the mask's edge
+            Some(mask) => {
+                let pixel_size = PLAYER_SPRITE_SCALE * power_ups.player_scale(&tuning);
+                let half = FRAME_SIZE as f32 / 2.0;
+
+                for y in 0..FRAME_SIZE as i32 {
+                    for x in 0..FRAME_SIZE as i32 {
+                        let edge = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|(dx, dy)| !mask.is_solid(x + dx, y + dy));
+                        if mask.is_solid(x, y) && edge {
+                            let center = position.0 + Vec2::new(x as f32 + 0.5 - half, half - y as f32 - 0.5) * pixel_size;
+                            gizmos.rect_2d(center, 0.0, Vec2::splat(pixel_size), Color::RED);
+                        }
+                    }
+                }
+            }
+            None => {
+                gizmos.circle_2d(player.body.center, player.body.circle.radius, Color::RED);
+                gizmos.circle_2d(player.head.center, player.head.circle.radius, Color::RED);
+            }
+        }
+    }
+
+    for wall in &walls {
+        let bottom_left = wall.bounding_box.min;
+        let top_right = wall.bounding_box.max;
+        gizmos.rect_2d((bottom_left + top_right) / 2.0, 0.0, top_right - bottom_left, Color::RED);
+    }
+
+    for pickup in &pickups {
+        gizmos.circle_2d(pickup.bounds.center, pickup.bounds.circle.radius, Color::BLUE);
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameMode, GameState};
+use crate::persistence::Storage;
+use crate::replay::recording;
+use crate::simulation::reset_rng;
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(DifficultySetting::default())
+        .add_event::<CycleDifficulty>()
+        .add_systems(PreStartup, load_difficulty)
+        .add_systems(Update, cycle_difficulty.run_if(on_event::<CycleDifficulty>()))
+        .add_systems(OnEnter(GameState::InProgress), choose_difficulty.before(reset_rng).run_if(recording));
+}
+
+/// How hard the run in progress is. See [`DifficultySetting`] for the player's choice.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Insane,
+}
+
+/// Multipliers applied to the tuning, relative to [`Difficulty::Normal`].
+pub struct DifficultyPreset {
+    /// Of every hole size.
+    pub hole_size: f32,
+    /// Of how quickly holes are allowed to move further from the previous one.
+    pub hole_drift: f32,
+    pub wall_speed: f32,
+    pub wall_interval: f32,
+    pub gravity: f32,
+}
+
+impl Difficulty {
+    fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Insane,
+            Difficulty::Insane => Difficulty::Easy,
+        }
+    }
+
+    pub fn preset(self) -> DifficultyPreset {
+        match self {
+            Difficulty::Easy => DifficultyPreset { hole_size: 1.25, hole_drift: 0.6, wall_speed: 0.85, wall_interval: 1.2, gravity: 0.9 },
+            Difficulty::Normal => DifficultyPreset { hole_size: 1.0, hole_drift: 1.0, wall_speed: 1.0, wall_interval: 1.0, gravity: 1.0 },
+            Difficulty::Hard => DifficultyPreset { hole_size: 0.85, hole_drift: 1.5, wall_speed: 1.15, wall_interval: 0.9, gravity: 1.1 },
+            Difficulty::Insane => DifficultyPreset { hole_size: 0.7, hole_drift: 2.0, wall_speed: 1.3, wall_interval: 0.8, gravity: 1.2 },
+        }
+    }
+
+    /// Distinguishes the stored scores of each difficulty. Normal scores keep the keys they had before
+    /// there were difficulties.
+    pub fn storage_key(self, key: &str) -> String {
+        match self {
+            Difficulty::Normal => key.into(),
+            _ => format!("{} ({})", key, self.to_string().to_lowercase()),
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "Easy"),
+            Difficulty::Normal => write!(f, "Normal"),
+            Difficulty::Hard => write!(f, "Hard"),
+            Difficulty::Insane => write!(f, "Insane"),
+        }
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            "insane" => Ok(Difficulty::Insane),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The difficulty chosen on the menu, used for every classic run.
+#[derive(Resource, Default)]
+pub struct DifficultySetting(pub Difficulty);
+
+fn load_difficulty(
+    mut setting: ResMut<DifficultySetting>,
+    storage: Storage,
+) {
+    if let Some(stored) = storage.get::<Difficulty>("difficulty") {
+        setting.0 = stored;
+    }
+}
+
+#[derive(Event, Default)]
+pub struct CycleDifficulty;
+
+fn cycle_difficulty(
+    mut setting: ResMut<DifficultySetting>,
+    mut storage: Storage,
+) {
+    setting.0 = setting.0.next();
+    storage.set("difficulty", &setting.0);
+}
+
+// everyone plays the daily challenge at the same difficulty
+fn choose_difficulty(
+    setting: Res<DifficultySetting>,
+    mode: Res<GameMode>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    *difficulty = match *mode {
+        GameMode::Classic => setting.0,
+        GameMode::DailyChallenge => Difficulty::Normal,
+    };
+}
@@ -1,16 +1,27 @@
 use bevy::prelude::*;
 
-use crate::{GameState, handle_button_event, pause_time};
+use crate::{GameMode, GameState, handle_button_event, pause_time, spawn_button};
+use crate::daily::{DailyChallenge, finish_attempt};
+use crate::difficulty::Difficulty;
+use crate::generator::WallPattern;
+use crate::leaderboard::{Leaderboard, name_prompt, NameEntry, NamePrompt, record_run};
+use crate::replay::{play, ReplayMode, Replays};
+use crate::scoring::ScoringMode;
+use crate::seed::{Date, SeedMode, SeedSettings};
+use crate::simulation::Seed;
 
 pub fn plugin(app: &mut App) {
     app
-        .add_systems(OnEnter(GameState::GameOver), (pause_time, game_over))
+        .add_systems(OnEnter(GameState::GameOver), (pause_time, game_over.after(record_run).after(finish_attempt)))
         .add_event::<Restart>()
         .add_systems(Update, handle_button_event::<RestartButton, Restart, GameOverMenu>.run_if(in_state(GameState::GameOver)))
         .add_systems(Update, restart_game.run_if(in_state(GameState::GameOver).and_then(on_event::<Restart>())))
         .add_event::<BackToMenu>()
         .add_systems(Update, handle_button_event::<BackToMenuButton, BackToMenu, GameOverMenu>.run_if(in_state(GameState::GameOver)))
-        .add_systems(Update, back_to_menu.run_if(in_state(GameState::GameOver).and_then(on_event::<BackToMenu>())));
+        .add_systems(Update, back_to_menu.run_if(in_state(GameState::GameOver).and_then(on_event::<BackToMenu>())))
+        .add_event::<WatchReplay>()
+        .add_systems(Update, handle_button_event::<WatchReplayButton, WatchReplay, GameOverMenu>.run_if(in_state(GameState::GameOver)))
+        .add_systems(Update, watch_replay.run_if(in_state(GameState::GameOver).and_then(on_event::<WatchReplay>())));
 }
 
 fn restart_game(mut next_state: ResMut<NextState<GameState>>) {
@@ -21,6 +32,21 @@ fn back_to_menu(mut next_state: ResMut<NextState<GameState>>) {
     next_state.set(GameState::PreGame);
 }
 
+fn watch_replay(
+    replays: Res<Replays>,
+    mut mode: ResMut<ReplayMode>,
+    mut difficulty: ResMut<Difficulty>,
+    mut pattern: ResMut<WallPattern>,
+    mut seed: ResMut<Seed>,
+    mut time: ResMut<Time<Fixed>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let Some(replay) = replays.0.first() {
+        play(replay.clone(), &mut mode, &mut difficulty, &mut pattern, &mut seed, &mut time);
+    }
+    next_state.set(GameState::InProgress);
+}
+
 #[derive(Component)]
 struct GameOverMenu;
 
@@ -30,12 +56,39 @@ struct RestartButton;
 #[derive(Component)]
 struct BackToMenuButton;
 
+#[derive(Component)]
+struct WatchReplayButton;
+
+#[allow(clippy::too_many_arguments)]
 fn game_over(
     mut time: ResMut<Time<Virtual>>,
     mut commands: Commands,
+    seed: Res<Seed>,
+    settings: Res<SeedSettings>,
+    mode: Res<GameMode>,
+    scoring: Res<ScoringMode>,
+    daily: Res<DailyChallenge>,
+    name_entry: Option<Res<NameEntry>>,
+    leaderboard: Res<Leaderboard>,
 ) {
     time.pause();
 
+    let prompt = name_entry.as_ref().and_then(|entry| {
+        leaderboard.entries.get(entry.position).map(|leaderboard_entry| name_prompt(entry, &leaderboard_entry.name))
+    });
+
+    let seed_label = match (*mode, settings.mode) {
+        (GameMode::DailyChallenge, _) => format!(
+            "Daily Challenge {}\nToday's Best: {}, Attempts Remaining: {}",
+            daily.date, daily.best(*scoring), daily.attempts_remaining()
+        ),
+        (GameMode::Classic, SeedMode::Daily) => format!("Seed: {} (Daily, {})", seed.0, Date::today()),
+        (GameMode::Classic, _) => format!("Seed: {}", seed.0),
+    };
+
+    // out of daily attempts, the player goes back to the menu rather than starting a classic run
+    let can_restart = *mode != GameMode::DailyChallenge || daily.attempts_remaining() > 0;
+
     commands.spawn((
         NodeBundle {
             style: Style {
@@ -77,6 +130,7 @@ fn game_over(
                         height: Val::Percent(40.0),
                         justify_content: JustifyContent::Center,
                         align_items: AlignItems::Center,
+                        flex_direction: FlexDirection::Column,
                         ..default()
                     },
                     ..default()
@@ -95,6 +149,37 @@ fn game_over(
                         ..default()
                     }
                 );
+
+                parent.spawn(
+                    TextBundle {
+                        text: Text::from_section(
+                            seed_label,
+                            TextStyle {
+                                color: Color::BLACK,
+                                font_size: 30.0,
+                                ..default()
+                            }
+                        ),
+                        ..default()
+                    }
+                );
+
+                if let Some(prompt) = prompt {
+                    parent.spawn((
+                        TextBundle {
+                            text: Text::from_section(
+                                prompt,
+                                TextStyle {
+                                    color: Color::BLACK,
+                                    font_size: 30.0,
+                                    ..default()
+                                }
+                            ),
+                            ..default()
+                        },
+                        NamePrompt
+                    ));
+                }
             });
 
             parent.spawn(
@@ -110,65 +195,11 @@ fn game_over(
                     ..default()
                 }
             ).with_children(|parent| {
-                parent.spawn((
-                    ButtonBundle {
-                        style: Style {
-                            border: UiRect::all(Val::Px(3.0)),
-                            width: Val::Percent(80.0),
-                            height: Val::Percent(40.0),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
-                        background_color: Color::rgba(0.0, 1.0, 0.0, 0.5).into(),
-                        ..default()
-                    },
-                    RestartButton
-                )).with_children(|parent| {
-                    parent.spawn(
-                        TextBundle {
-                            text: Text::from_section(
-                                "Start New Game",
-                                TextStyle {
-                                    color: Color::BLACK,
-                                    font_size: 60.0,
-                                    ..default()
-                                }
-                            ),
-                            ..default()
-                        }
-                    );
-                });
-
-                parent.spawn((
-                    ButtonBundle {
-                        style: Style {
-                            border: UiRect::all(Val::Px(3.0)),
-                            width: Val::Percent(80.0),
-                            height: Val::Percent(40.0),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
-                        background_color: Color::rgba(0.0, 1.0, 0.0, 0.5).into(),
-                        ..default()
-                    },
-                    BackToMenuButton
-                )).with_children(|parent| {
-                    parent.spawn(
-                        TextBundle {
-                            text: Text::from_section(
-                                "Back to Menu",
-                                TextStyle {
-                                    color: Color::BLACK,
-                                    font_size: 60.0,
-                                    ..default()
-                                }
-                            ),
-                            ..default()
-                        }
-                    );
-                });
+                if can_restart {
+                    spawn_button(parent, RestartButton, "Start New Game", 28.0, 40.0);
+                }
+                spawn_button(parent, BackToMenuButton, "Back to Menu", 28.0, 40.0);
+                spawn_button(parent, WatchReplayButton, "Watch Replay", 28.0, 40.0);
             });
         });
     });
@@ -178,4 +209,7 @@ fn game_over(
 struct Restart;
 
 #[derive(Event, Default)]
-struct BackToMenu;
\ No newline at end of file
+struct BackToMenu;
+
+#[derive(Event, Default)]
+struct WatchReplay;
\ No newline at end of file
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::Playfield;
+use crate::tuning::GameTuning;
+
+// Where each pair of walls leaves its hole is decided by a WallGenerator. Generators draw all of their
+// randomness from the simulation's RNG, so a run is still fully determined by its seed and its pattern.
+
+/// A gap between a top and a bottom wall, in world units.
+#[derive(Clone, Copy, Debug)]
+pub struct Hole {
+    /// The height of the center of the hole.
+    pub height: f32,
+    pub size: f32,
+}
+
+/// Everything a generator knows about the hole it's generating.
+pub struct HoleContext<'a> {
+    /// Counts up from 0 each run.
+    pub index: u32,
+    /// The height of the previous hole, or 0 for the first.
+    pub previous_height: f32,
+    pub tuning: &'a GameTuning,
+    pub playfield: &'a Playfield,
+    /// The size of the player, which hole sizes are multiples of.
+    pub unit: f32,
+}
+
+impl HoleContext<'_> {
+    /// The smallest and largest hole sizes, which both shrink toward [`GameTuning::final_hole`] over time.
+    pub fn size_range(&self) -> (f32, f32) {
+        let tuning = self.tuning;
+        let shrink = 1.0 + self.index as f32 / tuning.hole_shrink_walls;
+        let min = (self.unit * tuning.final_hole) + (self.unit * (tuning.initial_min_hole - tuning.final_hole)) / shrink;
+        let max = (self.unit * tuning.final_hole) + (self.unit * (tuning.initial_max_hole - tuning.final_hole)) / shrink;
+        (min, max)
+    }
+
+    /// The furthest a hole may be from the previous one, which starts near 0 and increases toward the
+    /// playfield height over time.
+    pub fn max_drift(&self) -> f32 {
+        let index = self.index as f32;
+        self.playfield.height * index / (self.tuning.hole_drift_walls + index)
+    }
+
+    /// The furthest the center of a hole of this size can be from the middle of the playfield.
+    pub fn height_limit(&self, size: f32) -> f32 {
+        (self.playfield.height / 2.0 - size / 2.0 - self.tuning.hole_margin).max(0.0)
+    }
+}
+
+pub trait WallGenerator: Send + Sync {
+    /// Where the next hole goes. It's clamped to the playfield afterward, so needn't be.
+    fn next_hole(&mut self, context: &HoleContext, rng: &mut ChaCha8Rng) -> Hole;
+}
+
+/// Each hole is a random distance up or down from the previous one.
+#[derive(Default)]
+pub struct RandomWalk;
+
+impl WallGenerator for RandomWalk {
+    fn next_hole(&mut self, context: &HoleContext, rng: &mut ChaCha8Rng) -> Hole {
+        let (min, max) = context.size_range();
+        let size = rng.gen_range(min..=max);
+
+        let max_drift = context.max_drift();
+        let height = context.previous_height + rng.gen_range(-max_drift..=max_drift);
+
+        Hole { height, size }
+    }
+}
+
+const SINE_PERIOD_WALLS: f32 = 12.0;
+
+/// Holes follow a sine wave, which starts flat and grows taller over time, making a winding corridor.
+#[derive(Default)]
+pub struct SineWave {
+    phase: Option<f32>,
+}
+
+impl WallGenerator for SineWave {
+    fn next_hole(&mut self, context: &HoleContext, rng: &mut ChaCha8Rng) -> Hole {
+        let phase = *self.phase.get_or_insert_with(|| rng.gen_range(0.0..TAU));
+
+        let (min, max) = context.size_range();
+        let size = (min + max) / 2.0;
+
+        let amplitude = context.max_drift().min(context.height_limit(size));
+        let height = amplitude * (phase + TAU * context.index as f32 / SINE_PERIOD_WALLS).sin();
+
+        Hole { height, size }
+    }
+}
+
+/// Holes climb or descend in equal steps for a few walls at a time, turning back at the playfield's edge.
+#[derive(Default)]
+pub struct Staircase {
+    direction: f32,
+    steps_remaining: u32,
+}
+
+impl WallGenerator for Staircase {
+    fn next_hole(&mut self, context: &HoleContext, rng: &mut ChaCha8Rng) -> Hole {
+        if self.steps_remaining == 0 {
+            self.direction = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+            self.steps_remaining = rng.gen_range(3..=6);
+        }
+        self.steps_remaining -= 1;
+
+        let (min, _) = context.size_range();
+        let size = min;
+
+        let step = (context.unit * 0.75).min(context.max_drift());
+        let limit = context.height_limit(size);
+        if (context.previous_height + self.direction * step).abs() > limit {
+            self.direction = -self.direction;
+        }
+
+        Hole { height: context.previous_height + self.direction * step, size }
+    }
+}
+
+/// Tight holes alternate with loose ones, with the loose ones allowed to drift further.
+#[derive(Default)]
+pub struct Alternating;
+
+impl WallGenerator for Alternating {
+    fn next_hole(&mut self, context: &HoleContext, rng: &mut ChaCha8Rng) -> Hole {
+        let (min, max) = context.size_range();
+        let loose = context.index % 2 == 1;
+
+        let (size, max_drift) = if loose {
+            (max, context.max_drift())
+        } else {
+            (min, context.max_drift() / 4.0)
+        };
+        let height = context.previous_height + rng.gen_range(-max_drift..=max_drift);
+
+        Hole { height, size }
+    }
+}
+
+/// A hole in an [`AuthoredPattern`].
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct AuthoredHole {
+    /// From -1 at the bottom of the playfield to 1 at the top.
+    pub height: f32,
+    /// In multiples of the player's size.
+    pub size: f32,
+}
+
+/// A hand-authored sequence of holes, repeated from the start once it runs out.
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct AuthoredPattern {
+    pub holes: Vec<AuthoredHole>,
+}
+
+impl AuthoredPattern {
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_str(text)
+    }
+}
+
+/// Plays an [`AuthoredPattern`] back without using the RNG at all.
+pub struct Authored(AuthoredPattern);
+
+impl WallGenerator for Authored {
+    fn next_hole(&mut self, context: &HoleContext, _rng: &mut ChaCha8Rng) -> Hole {
+        let holes = &self.0.holes;
+        if holes.is_empty() {
+            return Hole { height: 0.0, size: context.size_range().1 };
+        }
+
+        let hole = holes[context.index as usize % holes.len()];
+        Hole {
+            height: hole.height * context.playfield.height / 2.0,
+            size: hole.size * context.unit,
+        }
+    }
+}
+
+/// The authored patterns which have been loaded, by name.
+#[derive(Resource, Default)]
+pub struct AuthoredPatterns(pub HashMap<String, AuthoredPattern>);
+
+/// Which generator places the holes of the run in progress.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+pub enum WallPattern {
+    #[default]
+    RandomWalk,
+    SineWave,
+    Staircase,
+    Alternating,
+    /// One of the [`AuthoredPatterns`], by name.
+    Authored(String),
+}
+
+impl WallPattern {
+    pub const BUILT_IN: [WallPattern; 4] = [WallPattern::RandomWalk, WallPattern::SineWave, WallPattern::Staircase, WallPattern::Alternating];
+
+    pub fn generator(&self, authored: &AuthoredPatterns) -> Box<dyn WallGenerator> {
+        match self {
+            WallPattern::RandomWalk => Box::<RandomWalk>::default(),
+            WallPattern::SineWave => Box::<SineWave>::default(),
+            WallPattern::Staircase => Box::<Staircase>::default(),
+            WallPattern::Alternating => Box::<Alternating>::default(),
+            WallPattern::Authored(name) => match authored.0.get(name) {
+                Some(pattern) => Box::new(Authored(pattern.clone())),
+                None => {
+                    warn!("no wall pattern named {}, using a random walk", name);
+                    Box::<RandomWalk>::default()
+                }
+            },
+        }
+    }
+
+    /// A name without spaces, e.g. for replays. Authored patterns are prefixed with `authored:`.
+    pub fn key(&self) -> String {
+        match self {
+            WallPattern::RandomWalk => "random-walk".into(),
+            WallPattern::SineWave => "sine-wave".into(),
+            WallPattern::Staircase => "staircase".into(),
+            WallPattern::Alternating => "alternating".into(),
+            WallPattern::Authored(name) => format!("authored:{}", name),
+        }
+    }
+
+    /// Distinguishes the stored scores of each pattern. Random walk scores keep the keys they had before
+    /// there were patterns.
+    pub fn storage_key(&self, key: &str) -> String {
+        match self {
+            WallPattern::RandomWalk => key.into(),
+            _ => format!("{} ({})", key, self.key()),
+        }
+    }
+}
+
+impl Display for WallPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WallPattern::RandomWalk => write!(f, "Random Walk"),
+            WallPattern::SineWave => write!(f, "Sine Wave"),
+            WallPattern::Staircase => write!(f, "Staircase"),
+            WallPattern::Alternating => write!(f, "Alternating"),
+            WallPattern::Authored(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for WallPattern {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random-walk" => Ok(WallPattern::RandomWalk),
+            "sine-wave" => Ok(WallPattern::SineWave),
+            "staircase" => Ok(WallPattern::Staircase),
+            "alternating" => Ok(WallPattern::Alternating),
+            _ => s.strip_prefix("authored:").filter(|name| !name.is_empty()).map(|name| WallPattern::Authored(name.into())).ok_or(()),
+        }
+    }
+}
+
+/// The generator for the run in progress, holding whatever state it keeps between holes.
+#[derive(Resource)]
+pub struct Generator(pub Box<dyn WallGenerator>);
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self(Box::<RandomWalk>::default())
+    }
+}
+
+pub fn reset_generator(
+    pattern: Res<WallPattern>,
+    authored: Res<AuthoredPatterns>,
+    mut generator: ResMut<Generator>,
+) {
+    generator.0 = pattern.generator(&authored);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::difficulty::Difficulty;
+    use crate::simulation::Playfield;
+    use crate::tuning::GameTuning;
+
+    use super::{Authored, AuthoredHole, AuthoredPattern, HoleContext, RandomWalk, WallGenerator};
+
+    fn context<'a>(tuning: &'a GameTuning, playfield: &'a Playfield, index: u32) -> HoleContext<'a> {
+        HoleContext { index, previous_height: 0.0, tuning, playfield, unit: tuning.player_radius * tuning.hole_scale }
+    }
+
+    /// The sizes of `count` holes generated by a random walk, each with the given index.
+    fn sizes(tuning: &GameTuning, index: impl Fn(u32) -> u32, count: u32) -> Vec<f32> {
+        let playfield = Playfield::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+
+        (0..count).map(|i| RandomWalk.next_hole(&context(tuning, &playfield, index(i)), &mut rng).size).collect()
+    }
+
+    fn largest(sizes: Vec<f32>) -> f32 {
+        sizes.into_iter().fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn hole_sizes_vary_between_the_smallest_and_largest() {
+        let tuning = GameTuning::default();
+        let unit = tuning.player_radius * tuning.hole_scale;
+        let sizes = sizes(&tuning, |_| 0, 100);
+
+        assert!(sizes.iter().all(|size| (unit * tuning.initial_min_hole..=unit * tuning.initial_max_hole).contains(size)));
+        let smallest = sizes.iter().copied().fold(f32::INFINITY, f32::min);
+        let largest = largest(sizes);
+        assert!(largest - smallest > unit, "sizes only ranged from {} to {}", smallest, largest);
+    }
+
+    #[test]
+    fn the_largest_holes_shrink_over_a_run() {
+        let playfield = Playfield::default();
+        let mut first_holes = Vec::new();
+
+        for difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Insane] {
+            let tuning = GameTuning::default().with_difficulty(difficulty);
+            let early = largest(sizes(&tuning, |i| i, 20));
+            let late = largest(sizes(&tuning, |i| 100 + i, 20));
+
+            // bigger than the smallest holes were ever allowed to be, and than any hole can be later on
+            assert!(early > context(&tuning, &playfield, 0).size_range().0, "{}: {}", difficulty, early);
+            assert!(early > context(&tuning, &playfield, 100).size_range().1, "{}: {} then {}", difficulty, early, late);
+            assert!(late <= context(&tuning, &playfield, 100).size_range().1);
+            first_holes.push(early);
+        }
+
+        // and the easier the difficulty, the bigger they start
+        assert!(first_holes.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", first_holes);
+    }
+
+    #[test]
+    fn authored_holes_are_smaller_on_harder_difficulties() {
+        let playfield = Playfield::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+
+        let sizes = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Insane].map(|difficulty| {
+            let tuning = GameTuning::default().with_difficulty(difficulty);
+            let mut authored = Authored(AuthoredPattern { holes: vec![AuthoredHole { height: 0.0, size: 3.0 }] });
+            authored.next_hole(&context(&tuning, &playfield, 0), &mut rng).size
+        });
+
+        assert!(sizes.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", sizes);
+        assert_eq!(sizes[1], 3.0 * GameTuning::default().player_radius * GameTuning::default().hole_scale);
+    }
+}
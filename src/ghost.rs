@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+
+use crate::{GameState, Mass, Player, Position, PreviousPosition, Velocity};
+use crate::collision::FlapAnimation;
+use crate::difficulty::{Difficulty, DifficultySetting};
+use crate::generator::{reset_generator, WallPattern};
+use crate::patterns::PatternSetting;
+use crate::persistence::Storage;
+use crate::replay::{recording, Replay, RunRecorded};
+use crate::seed::{SeedMode, SeedSettings};
+use crate::simulation::{apply_flap, Playfield, reset_player, reset_rng, Seed, SimulationSet, Tick, TickRate};
+use crate::tuning::GameTuning;
+
+// A ghost replays an earlier run on the same course as the live one: the same seed, difficulty, wall
+// pattern and tick rate. It falls and flaps like the player, but nothing collides with it, and it fades out
+// where its run ended. On desktop, a replay saved by running with `--save-replays` can be raced with
+//   cargo run -- --ghost replays/42-1718000000.replay
+
+/// Personal bests are kept for this many courses, most recently improved first.
+const MAX_GHOSTS: usize = 20;
+
+/// Seconds the ghost takes to fade out after its run ends.
+pub const GHOST_FADE_TIME: f32 = 1.0;
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(PersonalBests::default())
+        .insert_resource(LoadedGhost::default())
+        .add_systems(Startup, load_personal_bests)
+        .add_systems(PostStartup, load_requested_ghost)
+        .add_systems(Update, save_personal_best.run_if(on_event::<RunRecorded>()))
+        .add_systems(OnEnter(GameState::InProgress), (despawn_ghosts, spawn_ghost.after(reset_rng).after(reset_generator).after(reset_player).run_if(recording)).chain())
+        .add_systems(OnEnter(GameState::PreGame), despawn_ghosts)
+        .add_systems(FixedUpdate, fly_ghosts.in_set(SimulationSet::Input));
+}
+
+#[derive(Component)]
+pub struct Ghost {
+    /// The tick on which the ghost's run ended.
+    ticks: u64,
+    flaps: Vec<u64>,
+    pub animation: FlapAnimation,
+    /// Seconds of simulated time since the ghost's run ended.
+    ended_for: Option<f32>,
+}
+
+impl Ghost {
+    /// From 1 while the ghost's run goes on, down to 0 once it has faded out.
+    pub fn opacity(&self) -> f32 {
+        self.ended_for.map_or(1.0, |ended_for| (1.0 - ended_for / GHOST_FADE_TIME).max(0.0))
+    }
+}
+
+/// Whether a replay was recorded on the course the next run will be played on.
+fn same_course(replay: &Replay, difficulty: Difficulty, pattern: &WallPattern, seed: u64, tick_rate: f64) -> bool {
+    replay.difficulty == difficulty && replay.pattern == *pattern && replay.seed == seed && replay.tick_rate == tick_rate
+}
+
+/// The longest run on each course the player has played, as replays.
+#[derive(Resource, Default)]
+struct PersonalBests(Vec<Replay>);
+
+fn load_personal_bests(
+    mut bests: ResMut<PersonalBests>,
+    storage: Storage,
+) {
+    if let Some(encoded) = storage.get::<Vec<String>>("ghosts") {
+        bests.0 = encoded.iter().filter_map(|replay| replay.parse().ok()).collect();
+    }
+}
+
+fn save_personal_best(
+    mut reader: EventReader<RunRecorded>,
+    mut bests: ResMut<PersonalBests>,
+    mut storage: Storage,
+) {
+    for recorded in reader.read() {
+        let run = &recorded.0;
+        let previous = bests.0.iter().position(|best| same_course(best, run.difficulty, &run.pattern, run.seed, run.tick_rate));
+
+        if let Some(index) = previous {
+            if bests.0[index].ticks >= run.ticks {
+                continue;
+            }
+            bests.0.remove(index);
+        }
+
+        bests.0.insert(0, run.clone());
+        bests.0.truncate(MAX_GHOSTS);
+
+        let encoded: Vec<String> = bests.0.iter().map(Replay::to_string).collect();
+        storage.set("ghosts", &encoded);
+    }
+}
+
+/// A replay passed with `--ghost <path>`, raced instead of the personal best while its course is played.
+#[derive(Resource, Default)]
+struct LoadedGhost(Option<Replay>);
+
+// the loaded ghost's course is chosen for this session only, and isn't stored
+fn load_requested_ghost(
+    mut loaded: ResMut<LoadedGhost>,
+    mut seed_settings: ResMut<SeedSettings>,
+    mut seed: ResMut<Seed>,
+    mut difficulty: ResMut<DifficultySetting>,
+    mut pattern: ResMut<PatternSetting>,
+    tick_rate: Res<TickRate>,
+) {
+    let Some(path) = std::env::args().skip_while(|arg| arg != "--ghost").nth(1) else {
+        return;
+    };
+
+    let replay = match std::fs::read_to_string(&path).map_err(|err| err.to_string()).and_then(|replay| replay.parse::<Replay>().map_err(|err| err.to_string())) {
+        Ok(replay) => replay,
+        Err(err) => {
+            error!("failed to load ghost {}: {}", path, err);
+            return;
+        }
+    };
+
+    if replay.tick_rate != tick_rate.0 {
+        warn!("ghost {} was recorded at {} Hz, run with --tick-rate {} to race it", path, replay.tick_rate, replay.tick_rate);
+    }
+
+    seed_settings.mode = SeedMode::Fixed;
+    seed_settings.fixed = replay.seed;
+    seed.0 = replay.seed;
+    difficulty.0 = replay.difficulty;
+    pattern.0 = replay.pattern.clone();
+    loaded.0 = Some(replay);
+}
+
+fn despawn_ghosts(
+    mut commands: Commands,
+    ghosts: Query<Entity, With<Ghost>>,
+) {
+    for entity in &ghosts {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_ghost(
+    mut commands: Commands,
+    loaded: Res<LoadedGhost>,
+    bests: Res<PersonalBests>,
+    difficulty: Res<Difficulty>,
+    pattern: Res<WallPattern>,
+    seed: Res<Seed>,
+    tick_rate: Res<TickRate>,
+    player: Query<&Position, With<Player>>,
+) {
+    let on_course = |replay: &&Replay| same_course(replay, *difficulty, &pattern, seed.0, tick_rate.0);
+    let Some(replay) = loaded.0.iter().find(on_course).or_else(|| bests.0.iter().find(on_course)) else {
+        return;
+    };
+
+    // drawn behind the player
+    let start = player.single().0;
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(start.extend(-0.5))),
+        Position(start),
+        PreviousPosition(start),
+        Mass,
+        Velocity::default(),
+        Ghost {
+            ticks: replay.ticks,
+            flaps: replay.flaps.clone(),
+            animation: FlapAnimation::default(),
+            ended_for: None,
+        },
+    ));
+}
+
+// runs before the simulation steps, so a ghost flaps on the same tick as the player it recorded did
+fn fly_ghosts(
+    mut commands: Commands,
+    time: Res<Time>,
+    tick: Res<Tick>,
+    playfield: Res<Playfield>,
+    tuning: Res<GameTuning>,
+    mut ghosts: Query<(Entity, &mut Ghost, &mut Velocity, &Position)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut ghost, mut velocity, position) in &mut ghosts {
+        if let Some(ended_for) = &mut ghost.ended_for {
+            *ended_for += dt;
+            continue;
+        }
+
+        // the tick the run ended on was still simulated
+        if tick.0 > ghost.ticks {
+            ghost.ended_for = Some(0.0);
+            velocity.0 = Vec2::ZERO;
+            commands.entity(entity).remove::<Mass>();
+            continue;
+        }
+
+        if ghost.flaps.binary_search(&tick.0).is_ok() {
+            apply_flap(&mut velocity, position, &playfield, &tuning);
+            ghost.animation.0 = 0.0;
+        } else {
+            ghost.animation.0 += dt;
+        }
+    }
+}
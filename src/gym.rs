@@ -0,0 +1,256 @@
+use std::time::Duration;
+
+use bevy::math::bounding::Bounded2d;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::{GameState, Player, Position, Velocity, Wall, WallSide};
+use crate::headless;
+use crate::simulation::{Flap, Playfield, Seed, WallsCleared};
+
+// An Environment is the game without a window, for training agents against, stepped one fixed tick at a
+// time like a gym environment:
+//
+//   let mut env = Environment::new();
+//   let mut observation = env.reset(seed);
+//   loop {
+//       let step = env.step(agent.act(&observation));
+//       if step.done {
+//           observation = env.reset(next_seed);
+//       } else {
+//           observation = step.observation;
+//       }
+//   }
+//
+// Every Environment has an App of its own, so any number can be stepped at once, e.g. one per thread.
+
+/// What an agent can do on each tick.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Wait,
+    Flap,
+}
+
+/// Where a hole is, relative to the player. A hole which hasn't been spawned yet is taken to be level with
+/// the player, a playfield's width ahead.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Gap {
+    /// How far ahead the leading edge of its walls is, which is negative while the player is between them.
+    pub dx: f32,
+    /// How far above the player the middle of the hole is.
+    pub dy: f32,
+}
+
+/// What an agent sees of the game.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Observation {
+    /// The player's height, from 0 in the middle of the playfield.
+    pub y: f32,
+    /// The player's vertical speed, positive upward.
+    pub velocity: f32,
+    /// The next two holes the player has yet to get past, nearest first.
+    pub gaps: [Gap; 2],
+}
+
+impl Observation {
+    /// `[y, velocity, dx, dy, dx, dy]`, e.g. as the input to a network.
+    pub fn to_array(&self) -> [f32; 6] {
+        let [next, after] = self.gaps;
+        [self.y, self.velocity, next.dx, next.dy, after.dx, after.dy]
+    }
+}
+
+/// What came of a step.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    /// Whether the player crashed, ending the run. Steps after that do nothing until the next reset.
+    pub done: bool,
+}
+
+/// How much each step is worth.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rewards {
+    /// For every tick survived...
+    pub tick: f32,
+    /// ...for every pair of walls cleared...
+    pub wall: f32,
+    /// ...and for crashing, instead of the rest.
+    pub crash: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            tick: 0.01,
+            wall: 1.0,
+            crash: -1.0,
+        }
+    }
+}
+
+pub struct Environment {
+    app: App,
+    pub rewards: Rewards,
+    /// The walls cleared before the latest step.
+    cleared: u32,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Environment {
+    /// Plays with the tuning, wall patterns and collision masks in the assets directory, where they can be
+    /// found, like a headless run does. [`Environment::reset`] has to be called before stepping.
+    pub fn new() -> Self {
+        let mut app = headless::app();
+        headless::load_assets(&mut app);
+
+        Self { app, rewards: Rewards::default(), cleared: 0 }
+    }
+
+    /// Starts a new run, on the course generated from `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.app.world.resource_mut::<Seed>().0 = seed;
+        self.app.world.resource_mut::<Events<Flap>>().clear();
+
+        // without any time passing, the run is restarted without simulating a tick of it
+        self.app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+
+        if *self.app.world.resource::<State<GameState>>().get() == GameState::InProgress {
+            self.app.world.resource_mut::<NextState<GameState>>().set(GameState::GameOver);
+            self.app.update();
+        }
+
+        self.app.world.resource_mut::<NextState<GameState>>().set(GameState::InProgress);
+        self.app.update();
+
+        headless::step_one_tick_per_update(&mut self.app);
+        self.cleared = 0;
+        self.observe()
+    }
+
+    /// Simulates the next tick of the run, flapping first if that's the action.
+    pub fn step(&mut self, action: Action) -> Step {
+        if self.over() {
+            return Step { observation: self.observe(), reward: 0.0, done: true };
+        }
+
+        if action == Action::Flap {
+            self.app.world.send_event(Flap);
+        }
+
+        self.app.update();
+
+        let done = self.over();
+        let cleared = self.app.world.resource::<WallsCleared>().0;
+        let reward = if done {
+            self.rewards.crash
+        } else {
+            self.rewards.tick + (cleared - self.cleared) as f32 * self.rewards.wall
+        };
+        self.cleared = cleared;
+
+        Step { observation: self.observe(), reward, done }
+    }
+
+    /// Whether there's no run in progress, or it's just ended.
+    fn over(&self) -> bool {
+        let pending = &self.app.world.resource::<NextState<GameState>>().0;
+        *self.app.world.resource::<State<GameState>>().get() != GameState::InProgress || pending.is_some()
+    }
+
+    fn observe(&mut self) -> Observation {
+        let world = &mut self.app.world;
+        let (position, velocity) = world.query_filtered::<(&Position, &Velocity), With<Player>>().single(world);
+        let (position, velocity) = (position.0, velocity.0);
+
+        // the bounds of each hole the player isn't past yet: (index, leading edge, bottom, top)
+        let mut holes: Vec<(u32, f32, f32, f32)> = Vec::new();
+
+        for wall in world.query::<&Wall>().iter(world) {
+            let bounds = wall.rectangle.aabb_2d(wall.center, 0.0);
+            if bounds.max.x < position.x {
+                continue;
+            }
+
+            let hole = match holes.iter_mut().find(|(index, ..)| *index == wall.hole) {
+                Some(hole) => hole,
+                None => {
+                    holes.push((wall.hole, bounds.min.x, f32::NEG_INFINITY, f32::INFINITY));
+                    holes.last_mut().unwrap()
+                }
+            };
+
+            match wall.side {
+                WallSide::Top => hole.3 = bounds.min.y,
+                WallSide::Bottom => hole.2 = bounds.max.y,
+            }
+        }
+
+        holes.sort_by_key(|(index, ..)| *index);
+
+        let ahead = world.resource::<Playfield>().width;
+        let gap = |hole: Option<&(u32, f32, f32, f32)>| match hole {
+            Some(&(_, leading_edge, bottom, top)) => Gap { dx: leading_edge - position.x, dy: (bottom + top) / 2.0 - position.y },
+            None => Gap { dx: ahead, dy: 0.0 },
+        };
+
+        Observation {
+            y: position.y,
+            velocity: velocity.y,
+            gaps: [gap(holes.first()), gap(holes.get(1))],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Environment};
+
+    fn run(seed: u64, ticks: usize) -> Vec<[f32; 6]> {
+        let mut env = Environment::new();
+        let mut observations = vec![env.reset(seed).to_array()];
+
+        for tick in 0..ticks {
+            let action = if tick % 20 == 0 { Action::Flap } else { Action::Wait };
+            let step = env.step(action);
+            observations.push(step.observation.to_array());
+            if step.done {
+                break;
+            }
+        }
+
+        observations
+    }
+
+    #[test]
+    fn runs_are_determined_by_their_seed_and_actions() {
+        assert_eq!(run(7, 300), run(7, 300));
+        assert_ne!(run(7, 300), run(8, 300));
+    }
+
+    #[test]
+    fn resetting_starts_the_run_over() {
+        let mut env = Environment::new();
+        let first = env.reset(3);
+
+        while !env.step(Action::Wait).done {}
+
+        assert_eq!(env.reset(3), first);
+        assert!(!env.step(Action::Flap).done);
+    }
+
+    #[test]
+    fn environments_can_be_stepped_in_parallel() {
+        let threads: Vec<_> = (0..4).map(|seed| std::thread::spawn(move || run(seed, 200))).collect();
+
+        for (seed, thread) in threads.into_iter().enumerate() {
+            assert_eq!(thread.join().unwrap(), run(seed as u64, 200));
+        }
+    }
+}
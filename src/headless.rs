@@ -0,0 +1,238 @@
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::texture::{CompressedImageFormats, ImageSampler, ImageType};
+use bevy::time::TimeUpdateStrategy;
+
+use crate::GameState;
+use crate::autopilot;
+use crate::collision::{self, BIRD_PATH, PlayerMasks};
+use crate::difficulty::Difficulty;
+use crate::generator::{AuthoredPattern, AuthoredPatterns, WallPattern};
+use crate::patterns::{AUTHORED_PATTERNS, pattern_path};
+use crate::pickups;
+use crate::power_ups;
+use crate::replay::{self, play, Replay, ReplayMode};
+use crate::simulation::{self, Seed, Tick, TickRate};
+use crate::tuning::{GameTuning, TUNING_PATH};
+
+// Runs the simulation without a window or GPU, e.g. on CI:
+//   cargo run -- --headless 10000
+// or plays a run saved with `--save-replays` back, to reproduce a bug report:
+//   cargo run -- --headless --replay replays/42-1718000000.replay
+// or lets the autopilot fly seeds 0, 1, 2... to check that their courses can be survived:
+//   cargo run -- --headless 100000 --autopilot
+
+const DEFAULT_TICKS: u64 = 10_000;
+
+/// Seconds of simulated time after which an autopilot run counts as having survived its course.
+const AUTOPILOT_RUN_TIME: f64 = 120.0;
+
+/// Returns the number of ticks to simulate if the game was started with `--headless [ticks]`.
+pub fn requested_ticks() -> Option<u64> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--headless");
+    args.next()?;
+    Some(args.next().and_then(|ticks| ticks.parse().ok()).unwrap_or(DEFAULT_TICKS))
+}
+
+/// Returns the path of the replay to play back if the game was started with `--replay <path>`.
+fn requested_replay() -> Option<String> {
+    std::env::args().skip_while(|arg| arg != "--replay").nth(1)
+}
+
+fn autopilot_requested() -> bool {
+    std::env::args().any(|arg| arg == "--autopilot")
+}
+
+/// Builds an app which advances the simulation by exactly one fixed tick per [`App::update`].
+pub fn app() -> App {
+    let mut app = App::new();
+
+    app
+        .add_plugins(MinimalPlugins)
+        .init_state::<GameState>()
+        .add_plugins((simulation::plugin, collision::simulation_plugin, pickups::simulation_plugin, power_ups::simulation_plugin, autopilot::simulation_plugin))
+        // nothing is loaded without an asset server, unless load_assets reads it from disk
+        .insert_resource(PlayerMasks::Unavailable);
+
+    step_one_tick_per_update(&mut app);
+    app
+}
+
+/// Reads what would otherwise come from the asset server straight from the assets directory.
+pub fn load_assets(app: &mut App) {
+    load_tuning(app);
+    load_authored_patterns(app);
+    load_player_masks(app);
+}
+
+/// Without an asset server, the tuning file is read directly, so headless runs play like windowed ones.
+fn load_tuning(app: &mut App) {
+    let path = std::path::Path::new("assets").join(TUNING_PATH);
+
+    match std::fs::read_to_string(&path) {
+        Ok(text) => match GameTuning::from_ron(&text) {
+            Ok(tuning) => app.insert_resource(tuning),
+            Err(err) => {
+                warn!("{}, using the default tuning", err);
+                app
+            }
+        },
+        Err(err) => {
+            warn!("failed to read {}: {}, using the default tuning", path.display(), err);
+            app
+        }
+    };
+}
+
+pub fn step_one_tick_per_update(app: &mut App) {
+    let timestep = app.world.resource::<Time<Fixed>>().timestep();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+}
+
+/// Authored wall patterns are read directly too, so that replays which used one can be played back.
+fn load_authored_patterns(app: &mut App) {
+    let mut authored = AuthoredPatterns::default();
+
+    for name in AUTHORED_PATTERNS {
+        let path = std::path::Path::new("assets").join(pattern_path(name));
+
+        match std::fs::read_to_string(&path).map_err(|err| err.to_string()).and_then(|text| AuthoredPattern::from_ron(&text).map_err(|err| err.to_string())) {
+            Ok(pattern) => {
+                authored.0.insert(name.to_string(), pattern);
+            }
+            Err(err) => warn!("failed to load {}: {}", path.display(), err),
+        }
+    }
+
+    app.insert_resource(authored);
+}
+
+/// bird.png is decoded directly too, so that headless runs collide like windowed ones.
+fn load_player_masks(app: &mut App) {
+    app.insert_resource(read_player_masks());
+}
+
+/// The masks for bird.png in the assets directory, or [`PlayerMasks::Unavailable`] if it can't be read.
+pub fn read_player_masks() -> PlayerMasks {
+    let path = std::path::Path::new("assets").join(BIRD_PATH);
+
+    let image = std::fs::read(&path).map_err(|err| err.to_string()).and_then(|bytes| {
+        Image::from_buffer(&bytes, ImageType::Extension("png"), CompressedImageFormats::NONE, true, ImageSampler::Default, RenderAssetUsages::default())
+            .map_err(|err| err.to_string())
+    });
+
+    match image {
+        Ok(image) => PlayerMasks::from_image(&image).unwrap_or(PlayerMasks::Unavailable),
+        Err(err) => {
+            warn!("failed to load {}: {}, colliding with circles", path.display(), err);
+            PlayerMasks::Unavailable
+        }
+    }
+}
+
+pub fn run(ticks: u64) {
+    let mut app = app();
+    app.add_plugins(LogPlugin::default());
+    load_assets(&mut app);
+
+    if let Some(path) = requested_replay() {
+        match std::fs::read_to_string(&path).map_err(|err| err.to_string()).and_then(|replay| replay.parse::<Replay>().map_err(|err| err.to_string())) {
+            Ok(replay) => play_back(app, replay, ticks),
+            Err(err) => error!("failed to load replay {}: {}", path, err),
+        }
+        return;
+    }
+
+    if autopilot_requested() {
+        check_courses(app, ticks);
+        return;
+    }
+
+    let mut runs = 0;
+
+    for _ in 0..ticks {
+        let state = app.world.resource::<State<GameState>>().get().clone();
+        if state != GameState::InProgress {
+            runs += 1;
+            app.world.resource_mut::<NextState<GameState>>().set(GameState::InProgress);
+        }
+
+        app.update();
+    }
+
+    info!("simulated {} ticks across {} runs", ticks, runs);
+}
+
+// each run is flown on the next seed, until it crashes or has lasted long enough
+fn check_courses(mut app: App, ticks: u64) {
+    let max_run_ticks = (AUTOPILOT_RUN_TIME * app.world.resource::<TickRate>().0) as u64;
+
+    *app.world.resource_mut::<ReplayMode>() = ReplayMode::Autopilot;
+
+    let mut checked = 0;
+    let mut survived = 0;
+    let mut simulated = 0;
+
+    while simulated < ticks {
+        app.world.resource_mut::<Seed>().0 = checked;
+        app.world.resource_mut::<NextState<GameState>>().set(GameState::InProgress);
+
+        while simulated < ticks {
+            app.update();
+            simulated += 1;
+
+            if *app.world.resource::<State<GameState>>().get() == GameState::GameOver {
+                warn!("seed {} crashed on tick {}", checked, app.world.resource::<Tick>().0);
+                checked += 1;
+                break;
+            }
+
+            if app.world.resource::<Tick>().0 >= max_run_ticks {
+                survived += 1;
+                checked += 1;
+                app.world.resource_mut::<NextState<GameState>>().set(GameState::GameOver);
+                app.update();
+                break;
+            }
+        }
+    }
+
+    info!("survived {} of {} courses in {} ticks", survived, checked, simulated);
+}
+
+fn play_back(mut app: App, replay: Replay, ticks: u64) {
+    let recorded = replay.ticks;
+
+    app.add_plugins(replay::playback_plugin);
+
+    let mut difficulty = Difficulty::default();
+    let mut pattern = WallPattern::default();
+    let mut seed = Seed(0);
+    let mut mode = ReplayMode::Recording;
+    play(replay, &mut mode, &mut difficulty, &mut pattern, &mut seed, &mut app.world.resource_mut::<Time<Fixed>>());
+    app.world.insert_resource(difficulty);
+    app.world.insert_resource(pattern);
+    app.world.insert_resource(seed);
+    app.world.insert_resource(mode);
+
+    // the replay may have been recorded at a different tick rate
+    step_one_tick_per_update(&mut app);
+    app.world.resource_mut::<NextState<GameState>>().set(GameState::InProgress);
+
+    for _ in 0..ticks {
+        app.update();
+
+        if *app.world.resource::<State<GameState>>().get() == GameState::GameOver {
+            let tick = app.world.resource::<Tick>().0;
+            if tick == recorded {
+                info!("replay ended on tick {}, as recorded", tick);
+            } else {
+                warn!("replay ended on tick {}, but was recorded ending on tick {}", tick, recorded);
+            }
+            return;
+        }
+    }
+
+    warn!("replay still running after {} ticks, but was recorded ending on tick {}", ticks, recorded);
+}
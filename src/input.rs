@@ -0,0 +1,321 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::ecs::system::SystemParam;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::ui::UiSystem;
+use bevy::utils::HashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameState, PauseState};
+use crate::leaderboard::NameEntry;
+use crate::pause::PauseButton;
+use crate::persistence::Storage;
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(Bindings::default())
+        .insert_resource(Actions::default())
+        .insert_resource(Focus::default())
+        .add_systems(Startup, load_bindings)
+        // after the UI, which tells whether the pointer is over the pause button
+        .add_systems(PreUpdate, read_actions.after(InputSystem).after(UiSystem::Focus))
+        // before Update, where the menus handle their buttons being pressed; while a name is being entered,
+        // confirming submits it instead
+        .add_systems(PreUpdate, (navigate_focus, press_focused.run_if(not(resource_exists::<NameEntry>))).chain().after(read_actions).run_if(in_menu))
+        .add_systems(PostUpdate, show_focus);
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Flap,
+    Pause,
+    Confirm,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [Action::Flap, Action::Pause, Action::Confirm];
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Flap => write!(f, "Flap"),
+            Action::Pause => write!(f, "Pause"),
+            Action::Confirm => write!(f, "Confirm"),
+        }
+    }
+}
+
+/// A physical input which can trigger an [`Action`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+    Touch,
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Key(key) => {
+                let name = format!("{:?}", key);
+                write!(f, "{}", name.strip_prefix("Key").unwrap_or(&name))
+            }
+            Binding::Mouse(button) => write!(f, "Mouse {:?}", button),
+            Binding::Gamepad(button) => write!(f, "Pad {:?}", button),
+            Binding::Touch => write!(f, "Touch"),
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Bindings {
+    pub flap: Vec<Binding>,
+    pub pause: Vec<Binding>,
+    pub confirm: Vec<Binding>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            flap: vec![
+                Binding::Key(KeyCode::Space),
+                Binding::Key(KeyCode::ArrowUp),
+                Binding::Mouse(MouseButton::Left),
+                Binding::Mouse(MouseButton::Right),
+                Binding::Mouse(MouseButton::Middle),
+                Binding::Touch,
+                Binding::Gamepad(GamepadButtonType::South),
+            ],
+            pause: vec![
+                Binding::Key(KeyCode::Escape),
+                Binding::Key(KeyCode::KeyP),
+                Binding::Gamepad(GamepadButtonType::Start),
+            ],
+            confirm: vec![
+                Binding::Key(KeyCode::Enter),
+                Binding::Key(KeyCode::NumpadEnter),
+                Binding::Gamepad(GamepadButtonType::South),
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, action: Action) -> &Vec<Binding> {
+        match action {
+            Action::Flap => &self.flap,
+            Action::Pause => &self.pause,
+            Action::Confirm => &self.confirm,
+        }
+    }
+
+    /// Binds the input to the action, or unbinds it if it was already bound.
+    pub fn toggle(&mut self, action: Action, binding: Binding) {
+        let bindings = match action {
+            Action::Flap => &mut self.flap,
+            Action::Pause => &mut self.pause,
+            Action::Confirm => &mut self.confirm,
+        };
+
+        if let Some(index) = bindings.iter().position(|bound| *bound == binding) {
+            bindings.remove(index);
+        } else {
+            bindings.push(binding);
+        }
+    }
+}
+
+fn load_bindings(
+    mut bindings: ResMut<Bindings>,
+    storage: Storage,
+) {
+    if let Some(stored) = storage.get::<Bindings>("bindings") {
+        *bindings = stored;
+    }
+}
+
+/// The raw inputs a frame's actions are read from.
+#[derive(SystemParam)]
+pub struct Inputs<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    touches: Res<'w, Touches>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+}
+
+impl Inputs<'_> {
+    fn just_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keys.just_pressed(key),
+            Binding::Mouse(button) => self.mouse.just_pressed(button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| self.gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button))),
+            Binding::Touch => self.touches.any_just_pressed(),
+        }
+    }
+
+    /// The first input pressed this frame, if any.
+    fn any_just_pressed(&self) -> Option<Binding> {
+        self.keys.get_just_pressed().next().map(|key| Binding::Key(*key))
+            .or_else(|| self.mouse.get_just_pressed().next().map(|button| Binding::Mouse(*button)))
+            .or_else(|| self.gamepad_buttons.get_just_pressed().next().map(|button| Binding::Gamepad(button.button_type)))
+            .or_else(|| self.touches.any_just_pressed().then_some(Binding::Touch))
+    }
+}
+
+/// Whether any key, button or touch was pressed this frame, bound to an action or not.
+pub fn any_input_just_pressed(inputs: Inputs) -> bool {
+    inputs.any_just_pressed().is_some()
+}
+
+/// Present while waiting for an input to bind to (or unbind from) the action.
+#[derive(Resource)]
+pub struct Rebinding(pub Action);
+
+/// The actions triggered this frame.
+#[derive(Resource, Default)]
+pub struct Actions {
+    just_pressed: HashSet<Action>,
+    /// Whether this frame's input was taken by [`Rebinding`], and so shouldn't do anything else.
+    consumed: bool,
+}
+
+fn read_actions(
+    mut commands: Commands,
+    rebinding: Option<Res<Rebinding>>,
+    mut bindings: ResMut<Bindings>,
+    inputs: Inputs,
+    pause_button: Query<&Interaction, With<PauseButton>>,
+    mut actions: ResMut<Actions>,
+    mut storage: Storage,
+) {
+    actions.just_pressed.clear();
+    actions.consumed = false;
+
+    if let Some(rebinding) = rebinding {
+        if let Some(binding) = inputs.any_just_pressed() {
+            bindings.toggle(rebinding.0, binding);
+            storage.set("bindings", &*bindings);
+            commands.remove_resource::<Rebinding>();
+            actions.consumed = true;
+        }
+        return;
+    }
+
+    // clicking or tapping the on-screen pause button shouldn't also flap, but other inputs still should while
+    // the cursor rests on it
+    let on_pause_button = |binding: Binding| match binding {
+        Binding::Mouse(_) => pause_button.iter().any(|interaction| *interaction != Interaction::None),
+        Binding::Touch => pause_button.iter().any(|interaction| *interaction == Interaction::Pressed),
+        Binding::Key(_) | Binding::Gamepad(_) => false,
+    };
+
+    for action in Action::ALL {
+        let pressed = |binding: &Binding| inputs.just_pressed(*binding) && !(action == Action::Flap && on_pause_button(*binding));
+        if bindings.get(action).iter().any(pressed) {
+            actions.just_pressed.insert(action);
+        }
+    }
+}
+
+/// Like [`input_just_pressed`](bevy::input::common_conditions::input_just_pressed), but for any input bound to the action.
+pub fn action_just_pressed(action: Action) -> impl FnMut(Res<Actions>) -> bool {
+    move |actions: Res<Actions>| actions.just_pressed.contains(&action)
+}
+
+/// The menu button selected with the keyboard or a gamepad, if any.
+#[derive(Resource, Default)]
+struct Focus(Option<Entity>);
+
+fn in_menu(
+    game_state: Res<State<GameState>>,
+    pause_state: Res<State<PauseState>>,
+    actions: Res<Actions>,
+) -> bool {
+    (*game_state.get() != GameState::InProgress || *pause_state.get() == PauseState::Paused) && !actions.consumed
+}
+
+// the pause button is hidden behind the pause menu, so it can't be focused
+type FocusableButtons<'w, 's> = Query<'w, 's, (Entity, &'static GlobalTransform), (With<Button>, Without<PauseButton>)>;
+
+fn navigate_focus(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    buttons: FocusableButtons,
+    mut focus: ResMut<Focus>,
+) {
+    let pressed = |key: KeyCode, button: GamepadButtonType| {
+        keys.just_pressed(key) || gamepads.iter().any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button)))
+    };
+
+    let step: isize = if pressed(KeyCode::ArrowDown, GamepadButtonType::DPadDown) || pressed(KeyCode::ArrowRight, GamepadButtonType::DPadRight) {
+        1
+    } else if pressed(KeyCode::ArrowUp, GamepadButtonType::DPadUp) || pressed(KeyCode::ArrowLeft, GamepadButtonType::DPadLeft) {
+        -1
+    } else {
+        return;
+    };
+
+    // top to bottom, then left to right
+    let mut order: Vec<(Entity, Vec3)> = buttons.iter().map(|(entity, transform)| (entity, transform.translation())).collect();
+    order.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    if order.is_empty() {
+        focus.0 = None;
+        return;
+    }
+
+    let next = match focus.0.and_then(|focused| order.iter().position(|(entity, _)| *entity == focused)) {
+        Some(index) => (index as isize + step).rem_euclid(order.len() as isize) as usize,
+        None if step > 0 => 0,
+        None => order.len() - 1,
+    };
+
+    focus.0 = Some(order[next].0);
+}
+
+// the focused button is pressed as if it were clicked, so every menu works without knowing about focus
+fn press_focused(
+    actions: Res<Actions>,
+    focus: Res<Focus>,
+    mut interactions: Query<&mut Interaction, With<Button>>,
+    mut pressed: Local<Option<Entity>>,
+) {
+    // a click is released by the UI when the mouse button is, but a confirm has to be released by hand
+    if let Some(entity) = pressed.take() {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            if *interaction == Interaction::Pressed {
+                *interaction = Interaction::None;
+            }
+        }
+    }
+
+    if !actions.just_pressed.contains(&Action::Confirm) {
+        return;
+    }
+
+    if let Some(mut interaction) = focus.0.and_then(|entity| interactions.get_mut(entity).ok()) {
+        *interaction = Interaction::Pressed;
+        *pressed = focus.0;
+    }
+}
+
+fn show_focus(
+    mut focus: ResMut<Focus>,
+    mut buttons: Query<(Entity, &mut BorderColor), With<Button>>,
+) {
+    if focus.0.is_some_and(|entity| !buttons.contains(entity)) {
+        focus.0 = None;
+    }
+
+    for (entity, mut border) in &mut buttons {
+        let color = if focus.0 == Some(entity) { Color::BLACK } else { Color::NONE };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use bevy::input::keyboard::KeyCode;
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameState, handle_button_event, Score, spawn_button};
+use crate::daily::in_daily_challenge;
+use crate::difficulty::{Difficulty, DifficultySetting};
+use crate::generator::WallPattern;
+use crate::patterns::PatternSetting;
+use crate::persistence::Storage;
+use crate::replay::{finish_run, recording};
+use crate::scoring::ScoringMode;
+use crate::seed::Date;
+use crate::simulation::{RANDOM_SEED, Seed, WallsCleared};
+
+pub const LEADERBOARD_SIZE: usize = 10;
+
+const MAX_NAME_LENGTH: usize = 12;
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(Leaderboard::default())
+        .insert_resource(PlayerName::default())
+        .add_systems(Startup, load_player_name)
+        .add_systems(Update, load_leaderboard.run_if(resource_changed::<ScoringMode>.or_else(resource_changed::<DifficultySetting>).or_else(resource_changed::<PatternSetting>)))
+        .add_systems(OnEnter(GameState::GameOver), record_run.before(finish_run).run_if(not(in_daily_challenge).and_then(recording)))
+        .add_systems(Update, enter_name.run_if(in_state(GameState::GameOver).and_then(resource_exists::<NameEntry>)))
+        .add_systems(OnExit(GameState::GameOver), finish_name_entry.run_if(resource_exists::<NameEntry>))
+        .add_systems(OnEnter(GameState::Leaderboard), leaderboard_screen)
+        .add_event::<CloseLeaderboard>()
+        .add_systems(Update, handle_button_event::<CloseLeaderboardButton, CloseLeaderboard, LeaderboardMenu>.run_if(in_state(GameState::Leaderboard)))
+        .add_systems(Update, close_leaderboard.run_if(in_state(GameState::Leaderboard).and_then(on_event::<CloseLeaderboard>())));
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: u64,
+    pub seed: u64,
+    pub date: Date,
+    pub walls: u32,
+}
+
+/// The best runs on this device, best first.
+#[derive(Resource, Serialize, Deserialize, Default)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    /// Inserts the entry if it makes the top ten, returning its position.
+    fn insert(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        if entry.score == 0 {
+            return None;
+        }
+
+        // ties go to the earlier run
+        let position = self.entries.iter().position(|other| entry.score > other.score).unwrap_or(self.entries.len());
+        if position >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        self.entries.insert(position, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        Some(position)
+    }
+}
+
+/// The name given to the last run which made the leaderboard, used as the default for the next one.
+#[derive(Resource)]
+pub struct PlayerName(String);
+
+impl Default for PlayerName {
+    fn default() -> Self {
+        Self("Player".into())
+    }
+}
+
+/// Present while the player is naming a run which made the leaderboard.
+#[derive(Resource)]
+pub struct NameEntry {
+    pub position: usize,
+}
+
+/// The text showing the name being entered.
+#[derive(Component)]
+pub struct NamePrompt;
+
+pub fn name_prompt(entry: &NameEntry, name: &str) -> String {
+    format!("New #{} run! Name: {}_", entry.position + 1, name)
+}
+
+fn load_leaderboard(
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut storage: Storage,
+) {
+    let key = scoring.leaderboard_key(difficulty.0, &pattern.0);
+    if let Some(stored) = storage.get::<Leaderboard>(&key) {
+        *leaderboard = stored;
+        return;
+    }
+
+    *leaderboard = Leaderboard::default();
+
+    // before the leaderboard, only a single high score was kept, and every run used the same seed
+    if *scoring == ScoringMode::Time && difficulty.0 == Difficulty::Normal && pattern.0 == WallPattern::RandomWalk {
+        if let Some(high_score) = storage.get::<u64>(&scoring.high_score_key(difficulty.0, &pattern.0)) {
+            leaderboard.insert(LeaderboardEntry {
+                name: "???".into(),
+                score: high_score,
+                seed: RANDOM_SEED,
+                date: Date::today(),
+                walls: 0,
+            });
+        }
+    }
+
+    storage.set(&key, &*leaderboard);
+}
+
+fn load_player_name(
+    mut name: ResMut<PlayerName>,
+    storage: Storage,
+) {
+    if let Some(stored) = storage.get::<String>("player name") {
+        name.0 = stored;
+    }
+}
+
+// only runs which reach game over are recorded, not those restarted or quit from the pause menu
+#[allow(clippy::too_many_arguments)]
+pub fn record_run(
+    mut commands: Commands,
+    score: Res<Score>,
+    seed: Res<Seed>,
+    walls_cleared: Res<WallsCleared>,
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+    name: Res<PlayerName>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut storage: Storage,
+) {
+    let entry = LeaderboardEntry {
+        name: name.0.clone(),
+        score: score.current,
+        seed: seed.0,
+        date: Date::today(),
+        walls: walls_cleared.0,
+    };
+
+    if let Some(position) = leaderboard.insert(entry) {
+        commands.insert_resource(NameEntry { position });
+        storage.set(&scoring.leaderboard_key(difficulty.0, &pattern.0), &*leaderboard);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn enter_name(
+    mut commands: Commands,
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<ButtonInput<KeyCode>>,
+    entry: Res<NameEntry>,
+    mut name: ResMut<PlayerName>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut prompt: Query<&mut Text, With<NamePrompt>>,
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+    mut storage: Storage,
+) {
+    for character in characters.read() {
+        for c in character.char.chars().filter(|c| !c.is_control()) {
+            if name.0.chars().count() < MAX_NAME_LENGTH {
+                name.0.push(c);
+            }
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Backspace) {
+        name.0.pop();
+    }
+
+    let Some(leaderboard_entry) = leaderboard.entries.get_mut(entry.position) else {
+        commands.remove_resource::<NameEntry>();
+        return;
+    };
+
+    if leaderboard_entry.name != name.0 {
+        leaderboard_entry.name = name.0.clone();
+
+        for mut text in &mut prompt {
+            text.sections[0].value = name_prompt(&entry, &name.0);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Enter) {
+        for mut text in &mut prompt {
+            text.sections[0].value = format!("Saved as {}", name.0);
+        }
+
+        save_name(&name, &leaderboard, *scoring, difficulty.0, &pattern.0, &mut storage);
+        commands.remove_resource::<NameEntry>();
+    }
+}
+
+fn finish_name_entry(
+    mut commands: Commands,
+    name: Res<PlayerName>,
+    leaderboard: Res<Leaderboard>,
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+    mut storage: Storage,
+) {
+    save_name(&name, &leaderboard, *scoring, difficulty.0, &pattern.0, &mut storage);
+    commands.remove_resource::<NameEntry>();
+}
+
+fn save_name(name: &PlayerName, leaderboard: &Leaderboard, scoring: ScoringMode, difficulty: Difficulty, pattern: &WallPattern, storage: &mut Storage) {
+    storage.set("player name", &name.0);
+    storage.set(&scoring.leaderboard_key(difficulty, pattern), leaderboard);
+}
+
+#[derive(Component)]
+struct LeaderboardMenu;
+
+#[derive(Component)]
+struct CloseLeaderboardButton;
+
+#[derive(Event, Default)]
+struct CloseLeaderboard;
+
+fn close_leaderboard(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::PreGame);
+}
+
+fn leaderboard_screen(
+    mut commands: Commands,
+    leaderboard: Res<Leaderboard>,
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+) {
+    let rows = if leaderboard.entries.is_empty() {
+        "No runs yet!".to_string()
+    } else {
+        leaderboard.entries.iter().enumerate()
+            .map(|(i, entry)| format!(
+                "{}. {} - {} (walls: {}, seed: {}, {})",
+                i + 1, entry.name, entry.score, entry.walls, entry.seed, entry.date
+            ))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                left: Val::Px(0.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.50).into(),
+            ..default()
+        },
+        LeaderboardMenu
+    )).with_children(|parent| {
+        parent.spawn(
+            NodeBundle {
+                style: Style {
+                    border: UiRect::all(Val::Px(3.0)),
+                    width: Val::Percent(60.0),
+                    min_width: Val::Px(550.0),
+                    height: Val::Percent(80.0),
+                    min_height: Val::Px(400.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceAround,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: Color::BLACK.into(),
+                background_color: Color::WHITE.into(),
+                ..default()
+            }
+        ).with_children(|parent| {
+            parent.spawn(
+                TextBundle {
+                    text: Text::from_section(
+                        format!("Leaderboard ({}, {}, {})", *scoring, difficulty.0, pattern.0),
+                        TextStyle {
+                            color: Color::BLACK,
+                            font_size: 80.0,
+                            ..default()
+                        }
+                    ),
+                    ..default()
+                }
+            );
+
+            parent.spawn(
+                TextBundle {
+                    text: Text::from_section(
+                        rows,
+                        TextStyle {
+                            color: Color::BLACK,
+                            font_size: 30.0,
+                            ..default()
+                        }
+                    ),
+                    ..default()
+                }
+            );
+
+            spawn_button(parent, CloseLeaderboardButton, "Back to Menu", 15.0, 40.0);
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+
+    use crate::difficulty::{Difficulty, DifficultySetting};
+    use crate::generator::WallPattern;
+    use crate::patterns::PatternSetting;
+    use crate::persistence::{Persistence, SaveFailed, Storage, Store};
+    use crate::scoring::ScoringMode;
+    use crate::seed::Date;
+
+    use super::{Leaderboard, LEADERBOARD_SIZE, LeaderboardEntry, load_leaderboard};
+
+    fn entry(name: &str, score: u64) -> LeaderboardEntry {
+        LeaderboardEntry { name: name.into(), score, seed: 42, date: Date::today(), walls: 0 }
+    }
+
+    fn scores(leaderboard: &Leaderboard) -> Vec<u64> {
+        leaderboard.entries.iter().map(|entry| entry.score).collect()
+    }
+
+    #[test]
+    fn runs_are_kept_best_first() {
+        let mut leaderboard = Leaderboard::default();
+
+        assert_eq!(leaderboard.insert(entry("a", 3)), Some(0));
+        assert_eq!(leaderboard.insert(entry("b", 7)), Some(0));
+        assert_eq!(leaderboard.insert(entry("c", 5)), Some(1));
+        assert_eq!(scores(&leaderboard), vec![7, 5, 3]);
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_run() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(entry("first", 5));
+
+        assert_eq!(leaderboard.insert(entry("second", 5)), Some(1));
+        assert_eq!(leaderboard.entries[0].name, "first");
+    }
+
+    #[test]
+    fn only_the_top_ten_are_kept() {
+        let mut leaderboard = Leaderboard::default();
+        for score in 1..=12 {
+            leaderboard.insert(entry("run", score));
+        }
+
+        assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+        assert_eq!(scores(&leaderboard), (3..=12).rev().collect::<Vec<_>>());
+        assert_eq!(leaderboard.insert(entry("too low", 3)), None);
+        assert_eq!(leaderboard.insert(entry("just enough", 4)), Some(9));
+        assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+    }
+
+    #[test]
+    fn runs_scoring_nothing_are_not_kept() {
+        let mut leaderboard = Leaderboard::default();
+
+        assert_eq!(leaderboard.insert(entry("crashed", 0)), None);
+        assert!(leaderboard.entries.is_empty());
+    }
+
+    fn app(scoring: ScoringMode, difficulty: Difficulty, pattern: WallPattern) -> App {
+        let mut app = App::new();
+        app
+            .add_event::<SaveFailed>()
+            .insert_resource(Store::new(&Persistence::Memory))
+            .insert_resource(scoring)
+            .insert_resource(DifficultySetting(difficulty))
+            .insert_resource(PatternSetting(pattern))
+            .insert_resource(Leaderboard::default());
+        app
+    }
+
+    /// Stores a high score as it was kept before there was a leaderboard, then loads the leaderboard.
+    fn load_with_high_score(app: &mut App, high_score: u64) -> Vec<(String, u64)> {
+        app.world.run_system_once(move |scoring: Res<ScoringMode>, difficulty: Res<DifficultySetting>, pattern: Res<PatternSetting>, mut storage: Storage| {
+            storage.set(&scoring.high_score_key(difficulty.0, &pattern.0), &high_score);
+        });
+        app.world.run_system_once(load_leaderboard);
+
+        app.world.resource::<Leaderboard>().entries.iter().map(|entry| (entry.name.clone(), entry.score)).collect()
+    }
+
+    #[test]
+    fn the_old_high_score_is_moved_onto_the_leaderboard_once() {
+        let mut app = app(ScoringMode::Time, Difficulty::Normal, WallPattern::RandomWalk);
+
+        assert_eq!(load_with_high_score(&mut app, 30), vec![("???".to_string(), 30)]);
+        // the stored leaderboard is loaded from then on, whatever happens to the old high score
+        assert_eq!(load_with_high_score(&mut app, 45), vec![("???".to_string(), 30)]);
+    }
+
+    #[test]
+    fn only_the_time_scores_of_normal_random_walks_are_moved() {
+        for (scoring, difficulty, pattern) in [
+            (ScoringMode::Walls, Difficulty::Normal, WallPattern::RandomWalk),
+            (ScoringMode::Time, Difficulty::Hard, WallPattern::RandomWalk),
+            (ScoringMode::Time, Difficulty::Normal, WallPattern::SineWave),
+        ] {
+            let mut app = app(scoring, difficulty, pattern.clone());
+            assert!(load_with_high_score(&mut app, 30).is_empty(), "{} {} {}", scoring, difficulty, pattern);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use bevy::math::bounding::{Aabb2d, BoundingCircle};
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy::window::PrimaryWindow;
+
+use crate::difficulty::DifficultySetting;
+use crate::patterns::PatternSetting;
+use crate::persistence::{HighScore, Storage, Store};
+use crate::replay::SaveReplays;
+use crate::scoring::ScoringMode;
+use crate::seed::RequestedSeed;
+
+pub use crate::collision::{FrameMask, PlayerMasks};
+pub use crate::persistence::{Persistence, SaveFailed};
+pub use crate::pickups::{Collected, Pickup, PickupKind};
+pub use crate::simulation::{Flap, HoleSpawned, Playfield, PreviousHole, Seed, Tick, WallsCleared};
+
+mod autopilot;
+mod collision;
+mod daily;
+#[cfg(feature = "debug")]
+mod debug;
+mod difficulty;
+mod game_over;
+mod generator;
+mod ghost;
+pub mod gym;
+pub mod headless;
+mod in_game;
+mod input;
+mod leaderboard;
+mod new_game;
+mod pause;
+mod patterns;
+mod persistence;
+mod pickups;
+mod power_ups;
+mod reachability;
+mod rendering;
+mod replay;
+mod scoring;
+mod seed;
+mod settings;
+mod simulation;
+mod tuning;
+
+/// The whole game, menus and all. It needs a window and an asset server, e.g. from `DefaultPlugins`, added
+/// before it; see [`headless`] for the simulation on its own.
+#[derive(Default)]
+pub struct FlappyPlugin {
+    /// The CSS selector of the canvas the primary window draws into, on the web.
+    pub canvas: Option<String>,
+    /// A seed to play, e.g. one which was shared, instead of the one chosen in the menu.
+    pub seed: Option<u64>,
+    pub persistence: Persistence,
+    /// Whether each recorded run is also written to `replays/` in the working directory, e.g. to be raced with
+    /// `--ghost`. Never with [`Persistence::Memory`].
+    pub save_replays: bool,
+}
+
+impl Plugin for FlappyPlugin {
+    fn build(&self, app: &mut App) {
+        // the primary window is spawned as soon as WindowPlugin is added, but only created once the app runs
+        if let Some(canvas) = &self.canvas {
+            let mut windows = app.world.query_filtered::<&mut Window, With<PrimaryWindow>>();
+            for mut window in windows.iter_mut(&mut app.world) {
+                window.canvas = Some(canvas.clone());
+            }
+        }
+
+        app
+            .insert_resource(Score::default())
+            .insert_resource(GameMode::default())
+            .insert_resource(Store::new(&self.persistence))
+            .init_state::<GameState>()
+            .init_state::<PauseState>()
+            .add_plugins((simulation::plugin, collision::plugin, tuning::plugin, rendering::plugin, persistence::plugin, input::plugin, scoring::plugin))
+            .add_plugins((game_over::plugin, new_game::plugin, in_game::plugin, pause::plugin, replay::plugin, seed::plugin, daily::plugin, leaderboard::plugin, settings::plugin, difficulty::plugin, patterns::plugin, pickups::plugin, power_ups::plugin, ghost::plugin, autopilot::plugin))
+            .insert_resource(RequestedSeed(self.seed))
+            .insert_resource(SaveReplays(self.save_replays && self.persistence != Persistence::Memory))
+            .add_systems(Startup, (setup, load_high_score).chain())
+            .add_systems(Update, (load_high_score, show_scores).chain().run_if(resource_changed::<ScoringMode>.or_else(resource_changed::<DifficultySetting>).or_else(resource_changed::<PatternSetting>)));
+
+        #[cfg(feature = "debug")]
+        app.add_plugins(debug::plugin);
+    }
+}
+
+#[derive(Component)]
+struct Scores;
+
+fn load_high_score(
+    mut score: ResMut<Score>,
+    mut high_score: ResMut<HighScore>,
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+    storage: Storage,
+) {
+    let stored = storage.get::<u64>(&scoring.high_score_key(difficulty.0, &pattern.0)).unwrap_or_default();
+    *high_score = HighScore { best: stored, stored };
+    score.high = stored;
+}
+
+fn scores_text(score: &Score, mode: GameMode, scoring: ScoringMode) -> String {
+    let high = match mode {
+        GameMode::Classic => "High Score",
+        GameMode::DailyChallenge => "Today's Best",
+    };
+
+    let current = match scoring {
+        ScoringMode::Time => "Current Score",
+        ScoringMode::Walls => "Walls Cleared",
+    };
+
+    format!("{}: {}\n{}: {}", high, score.high, current, score.current)
+}
+
+fn show_scores(
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    scoring: Res<ScoringMode>,
+    mut text: Query<&mut Text, With<Scores>>,
+) {
+    for mut text in &mut text {
+        text.sections[0].value = scores_text(&score, *mode, *scoring);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    score: Res<Score>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    commands.spawn((
+        TextBundle {
+            text: Text::from_sections([
+                TextSection::new(
+                    scores_text(&score, GameMode::Classic, ScoringMode::Time),
+                    TextStyle {
+                        color: Color::BLACK,
+                        font_size: 80.0,
+                        ..default()
+                    }
+                ),
+                TextSection::new(
+                    "",
+                    TextStyle {
+                        color: Color::BLACK,
+                        font_size: 40.0,
+                        ..default()
+                    }
+                ),
+                TextSection::new(
+                    "",
+                    TextStyle {
+                        color: Color::BLACK,
+                        font_size: 40.0,
+                        ..default()
+                    }
+                ),
+            ]),
+            ..default()
+        },
+        Scores
+    ));
+}
+
+#[derive(Component)]
+pub struct Mass;
+
+/// In world units per second.
+#[derive(Component, Default)]
+pub struct Velocity(pub Vec2);
+
+/// Where an entity is in the simulation. Its `Transform` follows, interpolated between fixed ticks.
+#[derive(Component, Default, Clone, Copy)]
+pub struct Position(pub Vec2);
+
+/// The [`Position`] at the start of the latest fixed tick.
+#[derive(Component, Default, Clone, Copy)]
+pub struct PreviousPosition(pub Vec2);
+
+#[derive(Component)]
+pub struct Player {
+    pub head: BoundingCircle,
+    pub body: BoundingCircle,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            head: BoundingCircle {
+                center: Vec2::new(0.0, 0.0),
+                circle: Circle::new(0.0),
+            },
+            body: BoundingCircle {
+                center: Vec2::new(0.0, 0.0),
+                circle: Circle::new(0.0),
+            },
+        }
+    }
+}
+
+#[derive(States, Default, Debug, Hash, Eq, PartialEq, Clone)]
+pub enum GameState {
+    #[default]
+    PreGame,
+    InProgress,
+    /// Passed through when a run is restarted from the pause menu, as a state can't transition to itself.
+    Restarting,
+    GameOver,
+    Leaderboard,
+    Settings,
+}
+
+/// Kept apart from [`GameState`] so that pausing doesn't leave [`GameState::InProgress`] and end the run.
+#[derive(States, Default, Debug, Hash, Eq, PartialEq, Clone)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+#[derive(Resource, Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    DailyChallenge,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WallSide {
+    Top,
+    Bottom,
+}
+
+#[derive(Component)]
+pub struct Wall {
+    pub side: WallSide,
+    /// The index of the hole this wall borders; the top and bottom walls of a pair share it.
+    pub hole: u32,
+    pub rectangle: Rectangle,
+    pub center: Vec2,
+    pub bounding_box: Aabb2d,
+}
+
+#[derive(Resource, Default)]
+pub struct Score {
+    pub high: u64,
+    pub current: u64,
+    pub stopwatch: Stopwatch,
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unpause_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn reset_score(mut score: ResMut<Score>) {
+    score.stopwatch.reset();
+}
+
+type InteractedButtons<'w, 's, B> = Query<'w, 's, (&'static Interaction, &'static mut BackgroundColor), (Changed<Interaction>, With<B>)>;
+
+fn handle_button_event<B: Component, E: Default + Event, D: Component>(
+    mut button: InteractedButtons<B>,
+    mut writer: EventWriter<E>,
+    mut commands: Commands,
+    menu_to_despawn: Query<Entity, With<D>>,
+) {
+    for (interaction, mut color) in &mut button {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 1.0).into();
+                writer.send(E::default());
+                commands.entity(menu_to_despawn.single()).despawn_recursive();
+            }
+            Interaction::Hovered => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 0.75).into();
+            }
+            Interaction::None => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 0.5).into();
+            }
+        }
+    }
+}
+
+/// Like [`handle_button_event`], but for buttons which change a setting and leave the menu open.
+fn handle_setting_button<B: Component, E: Default + Event>(
+    mut button: InteractedButtons<B>,
+    mut writer: EventWriter<E>,
+) {
+    for (interaction, mut color) in &mut button {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 1.0).into();
+                writer.send(E::default());
+            }
+            Interaction::Hovered => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 0.75).into();
+            }
+            Interaction::None => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 0.5).into();
+            }
+        }
+    }
+}
+
+/// Spawns a labelled menu button, `height` percent as tall as its parent.
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    button: impl Component,
+    label: impl Into<String>,
+    height: f32,
+    font_size: f32,
+) {
+    parent.spawn((
+        ButtonBundle {
+            style: Style {
+                border: UiRect::all(Val::Px(3.0)),
+                width: Val::Percent(80.0),
+                height: Val::Percent(height),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 1.0, 0.0, 0.5).into(),
+            ..default()
+        },
+        button
+    )).with_children(|parent| {
+        parent.spawn(
+            TextBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        color: Color::BLACK,
+                        font_size,
+                        ..default()
+                    }
+                ),
+                ..default()
+            }
+        );
+    });
+}
@@ -1,13 +1,41 @@
 use bevy::prelude::*;
 
-use crate::{despawn_all_walls, GameState, handle_button_event, pause_time, reset_score, reset_sprite};
+use crate::{GameMode, GameState, handle_button_event, handle_setting_button, pause_time, reset_score, spawn_button};
+use crate::autopilot::{AttractMode, ToggleAttractMode};
+use crate::daily::DailyChallenge;
+use crate::difficulty::{CycleDifficulty, DifficultySetting};
+use crate::patterns::{CycleWallPattern, PatternSetting};
+use crate::scoring::{CycleScoringMode, ScoringMode};
+use crate::seed::{CycleSeedMode, SeedSettings};
+use crate::simulation::{despawn_all_walls, reset_player};
 
 pub fn plugin(app: &mut App) {
     app
-        .add_systems(OnEnter(GameState::PreGame), (pause_time, reset_score, reset_sprite, despawn_all_walls, pre_game))
+        .add_systems(OnEnter(GameState::PreGame), (pause_time, reset_score, reset_player, despawn_all_walls, use_classic_mode, pre_game))
+        // a demo run can start without any button being pressed
+        .add_systems(OnExit(GameState::PreGame), despawn_new_game_menu)
         .add_systems(Update, handle_button_event::<NewGameButton, NewGame, NewGameMenu>.run_if(in_state(GameState::PreGame)))
         .add_event::<NewGame>()
-        .add_systems(Update, start_game.run_if(in_state(GameState::PreGame).and_then(on_event::<NewGame>())));
+        .add_systems(Update, start_game.run_if(in_state(GameState::PreGame).and_then(on_event::<NewGame>())))
+        .add_systems(Update, handle_button_event::<DailyChallengeButton, StartDailyChallenge, NewGameMenu>.run_if(in_state(GameState::PreGame)))
+        .add_event::<StartDailyChallenge>()
+        .add_systems(Update, start_daily_challenge.run_if(in_state(GameState::PreGame).and_then(on_event::<StartDailyChallenge>())))
+        .add_systems(Update, handle_button_event::<LeaderboardButton, ShowLeaderboard, NewGameMenu>.run_if(in_state(GameState::PreGame)))
+        .add_event::<ShowLeaderboard>()
+        .add_systems(Update, show_leaderboard.run_if(in_state(GameState::PreGame).and_then(on_event::<ShowLeaderboard>())))
+        .add_systems(Update, handle_button_event::<SettingsButton, ShowSettings, NewGameMenu>.run_if(in_state(GameState::PreGame)))
+        .add_event::<ShowSettings>()
+        .add_systems(Update, show_settings.run_if(in_state(GameState::PreGame).and_then(on_event::<ShowSettings>())))
+        .add_systems(Update, handle_setting_button::<SeedModeButton, CycleSeedMode>.run_if(in_state(GameState::PreGame)))
+        .add_systems(Update, show_seed_mode.run_if(in_state(GameState::PreGame).and_then(resource_changed::<SeedSettings>)))
+        .add_systems(Update, handle_setting_button::<ScoringModeButton, CycleScoringMode>.run_if(in_state(GameState::PreGame)))
+        .add_systems(Update, show_scoring_mode.run_if(in_state(GameState::PreGame).and_then(resource_changed::<ScoringMode>)))
+        .add_systems(Update, handle_setting_button::<DifficultyButton, CycleDifficulty>.run_if(in_state(GameState::PreGame)))
+        .add_systems(Update, show_difficulty.run_if(in_state(GameState::PreGame).and_then(resource_changed::<DifficultySetting>)))
+        .add_systems(Update, handle_setting_button::<WallPatternButton, CycleWallPattern>.run_if(in_state(GameState::PreGame)))
+        .add_systems(Update, show_wall_pattern.run_if(in_state(GameState::PreGame).and_then(resource_changed::<PatternSetting>)))
+        .add_systems(Update, handle_setting_button::<AttractModeButton, ToggleAttractMode>.run_if(in_state(GameState::PreGame)))
+        .add_systems(Update, show_attract_mode.run_if(in_state(GameState::PreGame).and_then(resource_changed::<AttractMode>)));
 }
 
 fn start_game(
@@ -16,13 +44,150 @@ fn start_game(
     next_state.set(GameState::InProgress);
 }
 
+fn show_leaderboard(
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    next_state.set(GameState::Leaderboard);
+}
+
+fn show_settings(
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    next_state.set(GameState::Settings);
+}
+
+fn despawn_new_game_menu(
+    mut commands: Commands,
+    menu: Query<Entity, With<NewGameMenu>>,
+) {
+    for entity in &menu {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn use_classic_mode(mut mode: ResMut<GameMode>) {
+    *mode = GameMode::Classic;
+}
+
+fn start_daily_challenge(
+    mut mode: ResMut<GameMode>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    *mode = GameMode::DailyChallenge;
+    next_state.set(GameState::InProgress);
+}
+
 #[derive(Component)]
 struct NewGameMenu;
 
 #[derive(Component)]
 struct NewGameButton;
 
-fn pre_game(mut commands: Commands) {
+#[derive(Component)]
+struct DailyChallengeButton;
+
+#[derive(Component)]
+struct SeedModeButton;
+
+#[derive(Component)]
+struct ScoringModeButton;
+
+#[derive(Component)]
+struct DifficultyButton;
+
+#[derive(Component)]
+struct WallPatternButton;
+
+#[derive(Component)]
+struct AttractModeButton;
+
+#[derive(Component)]
+struct LeaderboardButton;
+
+#[derive(Component)]
+struct SettingsButton;
+
+fn show_seed_mode(
+    settings: Res<SeedSettings>,
+    button: Query<&Children, With<SeedModeButton>>,
+    mut text: Query<&mut Text>,
+) {
+    for children in &button {
+        let mut iter = text.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = format!("Seed: {}", settings.mode);
+        }
+    }
+}
+
+fn show_scoring_mode(
+    scoring: Res<ScoringMode>,
+    button: Query<&Children, With<ScoringModeButton>>,
+    mut text: Query<&mut Text>,
+) {
+    for children in &button {
+        let mut iter = text.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = format!("Score: {}", *scoring);
+        }
+    }
+}
+
+fn show_difficulty(
+    difficulty: Res<DifficultySetting>,
+    button: Query<&Children, With<DifficultyButton>>,
+    mut text: Query<&mut Text>,
+) {
+    for children in &button {
+        let mut iter = text.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = format!("Difficulty: {}", difficulty.0);
+        }
+    }
+}
+
+fn show_wall_pattern(
+    pattern: Res<PatternSetting>,
+    button: Query<&Children, With<WallPatternButton>>,
+    mut text: Query<&mut Text>,
+) {
+    for children in &button {
+        let mut iter = text.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = format!("Walls: {}", pattern.0);
+        }
+    }
+}
+
+fn attract_mode_label(attract: AttractMode) -> String {
+    format!("Attract Mode: {}", if attract.0 { "On" } else { "Off" })
+}
+
+fn show_attract_mode(
+    attract: Res<AttractMode>,
+    button: Query<&Children, With<AttractModeButton>>,
+    mut text: Query<&mut Text>,
+) {
+    for children in &button {
+        let mut iter = text.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = attract_mode_label(*attract);
+        }
+    }
+}
+
+fn pre_game(
+    mut commands: Commands,
+    settings: Res<SeedSettings>,
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+    attract: Res<AttractMode>,
+    mut daily: ResMut<DailyChallenge>,
+) {
+    daily.refresh();
+    let attempts_remaining = daily.attempts_remaining();
+
     commands.spawn((
         NodeBundle {
             style: Style {
@@ -46,8 +211,8 @@ fn pre_game(mut commands: Commands) {
                     border: UiRect::all(Val::Px(3.0)),
                     width: Val::Percent(50.0),
                     min_width: Val::Px(550.0),
-                    height: Val::Percent(50.0),
-                    min_height: Val::Px(200.0),
+                    height: Val::Percent(70.0),
+                    min_height: Val::Px(400.0),
                     flex_direction: FlexDirection::Column,
                     ..default()
                 },
@@ -61,7 +226,7 @@ fn pre_game(mut commands: Commands) {
                 NodeBundle {
                     style: Style {
                         width: Val::Percent(100.0),
-                        height: Val::Percent(40.0),
+                        height: Val::Percent(30.0),
                         justify_content: JustifyContent::Center,
                         align_items: AlignItems::Center,
                         ..default()
@@ -88,47 +253,55 @@ fn pre_game(mut commands: Commands) {
                 NodeBundle {
                     style: Style {
                         width: Val::Percent(100.0),
-                        height: Val::Percent(60.0),
-                        justify_content: JustifyContent::Center,
+                        height: Val::Percent(70.0),
+                        justify_content: JustifyContent::SpaceAround,
                         align_items: AlignItems::Center,
+                        flex_direction: FlexDirection::Column,
                         ..default()
                     },
                     ..default()
                 }
             ).with_children(|parent| {
-                parent.spawn((
-                    ButtonBundle {
-                        style: Style {
-                            border: UiRect::all(Val::Px(3.0)),
-                            width: Val::Percent(80.0),
-                            height: Val::Percent(80.0),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
-                        background_color: Color::rgba(0.0, 1.0, 0.0, 0.5).into(),
-                        ..default()
-                    },
-                    NewGameButton
-                )).with_children(|parent| {
+                spawn_button(parent, NewGameButton, "Start New Game", 14.0, 60.0);
+
+                if attempts_remaining > 0 {
+                    spawn_button(parent, DailyChallengeButton, format!("Daily Challenge ({} left)", attempts_remaining), 9.0, 40.0);
+                } else {
                     parent.spawn(
                         TextBundle {
                             text: Text::from_section(
-                                "Start New Game",
+                                format!("Daily Challenge: best {} today, come back tomorrow!", daily.best(*scoring)),
                                 TextStyle {
                                     color: Color::BLACK,
-                                    font_size: 60.0,
+                                    font_size: 30.0,
                                     ..default()
                                 }
                             ),
                             ..default()
                         }
                     );
-                });
+                }
+
+                spawn_button(parent, DifficultyButton, format!("Difficulty: {}", difficulty.0), 9.0, 40.0);
+                spawn_button(parent, WallPatternButton, format!("Walls: {}", pattern.0), 9.0, 40.0);
+                spawn_button(parent, LeaderboardButton, "Leaderboard", 9.0, 40.0);
+                spawn_button(parent, SeedModeButton, format!("Seed: {}", settings.mode), 9.0, 40.0);
+                spawn_button(parent, ScoringModeButton, format!("Score: {}", *scoring), 9.0, 40.0);
+                spawn_button(parent, AttractModeButton, attract_mode_label(*attract), 9.0, 40.0);
+                spawn_button(parent, SettingsButton, "Controls", 9.0, 40.0);
             });
         });
     });
 }
 
 #[derive(Event, Default)]
-struct NewGame;
\ No newline at end of file
+struct NewGame;
+
+#[derive(Event, Default)]
+struct StartDailyChallenge;
+
+#[derive(Event, Default)]
+struct ShowLeaderboard;
+
+#[derive(Event, Default)]
+struct ShowSettings;
\ No newline at end of file
@@ -0,0 +1,146 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::asset::io::Reader;
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+
+use crate::{GameMode, GameState};
+use crate::generator::{AuthoredPattern, AuthoredPatterns, reset_generator, WallPattern};
+use crate::persistence::Storage;
+use crate::replay::recording;
+
+// Hand-authored wall patterns live in assets/patterns/<name>.pattern.ron. Every file has to be listed in
+// AUTHORED_PATTERNS, since the web build can't list the files in a directory.
+
+pub const AUTHORED_PATTERNS: [&str; 2] = ["zigzag", "tunnel"];
+
+pub fn pattern_path(name: &str) -> String {
+    format!("patterns/{}.pattern.ron", name)
+}
+
+pub fn plugin(app: &mut App) {
+    app
+        .init_asset::<AuthoredPattern>()
+        .register_asset_loader(AuthoredPatternLoader)
+        .insert_resource(PatternSetting::default())
+        .add_event::<CycleWallPattern>()
+        .add_systems(Startup, (load_pattern_setting, load_authored_patterns))
+        .add_systems(Update, apply_authored_patterns.run_if(on_event::<AssetEvent<AuthoredPattern>>()))
+        .add_systems(Update, cycle_wall_pattern.run_if(on_event::<CycleWallPattern>()))
+        .add_systems(OnEnter(GameState::InProgress), choose_pattern.before(reset_generator).run_if(recording));
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::Io(err) => write!(f, "failed to read wall pattern: {}", err),
+            PatternError::Ron(err) => write!(f, "failed to parse wall pattern: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+struct AuthoredPatternLoader;
+
+impl AssetLoader for AuthoredPatternLoader {
+    type Asset = AuthoredPattern;
+    type Settings = ();
+    type Error = PatternError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut text = String::new();
+            reader.read_to_string(&mut text).await.map_err(PatternError::Io)?;
+            AuthoredPattern::from_ron(&text).map_err(PatternError::Ron)
+        })
+    }
+
+    // distinct from tuning.ron, so each file gets the right loader
+    fn extensions(&self) -> &[&str] {
+        &["pattern.ron"]
+    }
+}
+
+/// Keeps the authored patterns loaded, so that they're reloaded when their files change.
+#[derive(Resource)]
+struct PatternHandles(Vec<(String, Handle<AuthoredPattern>)>);
+
+fn load_authored_patterns(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let handles = AUTHORED_PATTERNS.iter().map(|name| (name.to_string(), asset_server.load(pattern_path(name)))).collect();
+    commands.insert_resource(PatternHandles(handles));
+}
+
+fn apply_authored_patterns(
+    mut events: EventReader<AssetEvent<AuthoredPattern>>,
+    handles: Res<PatternHandles>,
+    assets: Res<Assets<AuthoredPattern>>,
+    mut authored: ResMut<AuthoredPatterns>,
+) {
+    for event in events.read() {
+        for (name, handle) in &handles.0 {
+            if event.is_loaded_with_dependencies(handle) || event.is_modified(handle) {
+                if let Some(loaded) = assets.get(handle) {
+                    authored.0.insert(name.clone(), loaded.clone());
+                }
+            }
+        }
+    }
+}
+
+/// The pattern chosen on the menu, used for every classic run.
+#[derive(Resource, Default)]
+pub struct PatternSetting(pub WallPattern);
+
+fn load_pattern_setting(
+    mut setting: ResMut<PatternSetting>,
+    storage: Storage,
+) {
+    if let Some(stored) = storage.get::<WallPattern>("wall pattern") {
+        setting.0 = stored;
+    }
+}
+
+#[derive(Event, Default)]
+pub struct CycleWallPattern;
+
+/// Steps through the built-in patterns, then the authored ones.
+fn cycle_wall_pattern(
+    mut setting: ResMut<PatternSetting>,
+    mut storage: Storage,
+) {
+    let all: Vec<WallPattern> = WallPattern::BUILT_IN.into_iter()
+        .chain(AUTHORED_PATTERNS.iter().map(|name| WallPattern::Authored(name.to_string())))
+        .collect();
+
+    let next = all.iter().position(|pattern| *pattern == setting.0).map_or(0, |index| (index + 1) % all.len());
+    setting.0 = all[next].clone();
+    storage.set("wall pattern", &setting.0);
+}
+
+// everyone plays the daily challenge's course the same way
+fn choose_pattern(
+    setting: Res<PatternSetting>,
+    mode: Res<GameMode>,
+    mut pattern: ResMut<WallPattern>,
+) {
+    *pattern = match *mode {
+        GameMode::Classic => setting.0.clone(),
+        GameMode::DailyChallenge => WallPattern::RandomWalk,
+    };
+}
@@ -0,0 +1,250 @@
+use bevy::prelude::*;
+
+use crate::{GameMode, GameState, handle_button_event, handle_setting_button, pause_time, PauseState, spawn_button};
+use crate::daily::DailyChallenge;
+use crate::input::{Action, action_just_pressed};
+use crate::persistence::window_hidden;
+use crate::replay::{autopiloting, ReplayMode};
+
+pub fn plugin(app: &mut App) {
+    app
+        .add_systems(OnEnter(GameState::InProgress), spawn_pause_button.run_if(not(autopiloting)))
+        .add_systems(OnExit(GameState::InProgress), (despawn_pause_button, stop_pausing))
+        .add_systems(Update, toggle_pause.run_if(in_state(GameState::InProgress).and_then(not(autopiloting)).and_then(action_just_pressed(Action::Pause))))
+        .add_systems(Update, pause.run_if(in_state(GameState::InProgress).and_then(in_state(PauseState::Running)).and_then(window_hidden)))
+        .add_event::<Pause>()
+        .add_systems(Update, handle_setting_button::<PauseButton, Pause>.run_if(in_state(GameState::InProgress)))
+        .add_systems(Update, pause.run_if(in_state(GameState::InProgress).and_then(on_event::<Pause>())))
+        .add_systems(OnEnter(PauseState::Paused), (pause_time, pause_menu))
+        .add_systems(OnExit(PauseState::Paused), despawn_pause_menu)
+        .add_event::<Resume>()
+        .add_systems(Update, handle_button_event::<ResumeButton, Resume, PauseMenu>.run_if(in_state(PauseState::Paused)))
+        .add_systems(Update, resume.run_if(in_state(PauseState::Paused).and_then(on_event::<Resume>())))
+        .add_event::<RestartRun>()
+        .add_systems(Update, handle_button_event::<RestartButton, RestartRun, PauseMenu>.run_if(in_state(PauseState::Paused)))
+        .add_systems(Update, restart_run.run_if(in_state(PauseState::Paused).and_then(on_event::<RestartRun>())))
+        .add_systems(OnEnter(GameState::Restarting), start_run)
+        .add_event::<QuitToMenu>()
+        .add_systems(Update, handle_button_event::<QuitButton, QuitToMenu, PauseMenu>.run_if(in_state(PauseState::Paused)))
+        .add_systems(Update, quit_to_menu.run_if(in_state(PauseState::Paused).and_then(on_event::<QuitToMenu>())));
+}
+
+fn toggle_pause(
+    state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    match state.get() {
+        PauseState::Running => next_state.set(PauseState::Paused),
+        PauseState::Paused => {
+            time.unpause();
+            next_state.set(PauseState::Running);
+        }
+    }
+}
+
+fn pause(mut next_state: ResMut<NextState<PauseState>>) {
+    next_state.set(PauseState::Paused);
+}
+
+fn resume(
+    mut next_state: ResMut<NextState<PauseState>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    time.unpause();
+    next_state.set(PauseState::Running);
+}
+
+fn restart_run(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Restarting);
+}
+
+// once the run being restarted has been left
+fn start_run(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::InProgress);
+}
+
+fn quit_to_menu(
+    mut next_state: ResMut<NextState<GameState>>,
+    mut replay_mode: ResMut<ReplayMode>,
+) {
+    // a replay which is quit part way through never reaches game over, where playback normally ends
+    *replay_mode = ReplayMode::Recording;
+    next_state.set(GameState::PreGame);
+}
+
+fn stop_pausing(mut next_state: ResMut<NextState<PauseState>>) {
+    next_state.set(PauseState::Running);
+}
+
+#[derive(Component)]
+pub struct PauseButton;
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+struct ResumeButton;
+
+#[derive(Component)]
+struct RestartButton;
+
+#[derive(Component)]
+struct QuitButton;
+
+fn spawn_pause_button(mut commands: Commands) {
+    commands.spawn((
+        ButtonBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(20.0),
+                right: Val::Px(20.0),
+                width: Val::Px(80.0),
+                height: Val::Px(80.0),
+                border: UiRect::all(Val::Px(3.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 1.0, 0.0, 0.5).into(),
+            ..default()
+        },
+        PauseButton
+    )).with_children(|parent| {
+        parent.spawn(
+            TextBundle {
+                text: Text::from_section(
+                    "II",
+                    TextStyle {
+                        color: Color::BLACK,
+                        font_size: 50.0,
+                        ..default()
+                    }
+                ),
+                ..default()
+            }
+        );
+    });
+}
+
+fn despawn_pause_button(
+    mut commands: Commands,
+    button: Query<Entity, With<PauseButton>>,
+) {
+    for entity in &button {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn despawn_pause_menu(
+    mut commands: Commands,
+    menu: Query<Entity, With<PauseMenu>>,
+) {
+    for entity in &menu {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn pause_menu(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    daily: Res<DailyChallenge>,
+) {
+    // out of daily attempts, the run can only be resumed or quit
+    let can_restart = *mode != GameMode::DailyChallenge || daily.attempts_remaining() > 0;
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                left: Val::Px(0.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.50).into(),
+            ..default()
+        },
+        PauseMenu
+    )).with_children(|parent| {
+        parent.spawn(
+            NodeBundle {
+                style: Style {
+                    border: UiRect::all(Val::Px(3.0)),
+                    width: Val::Percent(50.0),
+                    min_width: Val::Px(550.0),
+                    height: Val::Percent(50.0),
+                    min_height: Val::Px(200.0),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                border_color: Color::BLACK.into(),
+                background_color: Color::WHITE.into(),
+                ..default()
+            }
+        ).with_children(|parent| {
+
+            parent.spawn(
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(40.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                }
+            ).with_children(|parent| {
+                parent.spawn(
+                    TextBundle {
+                        text: Text::from_section(
+                            "Paused",
+                            TextStyle {
+                                color: Color::BLACK,
+                                font_size: 80.0,
+                                ..default()
+                            }
+                        ),
+                        ..default()
+                    }
+                );
+            });
+
+            parent.spawn(
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(60.0),
+                        justify_content: JustifyContent::SpaceAround,
+                        align_items: AlignItems::Center,
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                }
+            ).with_children(|parent| {
+                spawn_button(parent, ResumeButton, "Resume", 28.0, 40.0);
+                if can_restart {
+                    spawn_button(parent, RestartButton, "Restart", 28.0, 40.0);
+                }
+                spawn_button(parent, QuitButton, "Quit to Menu", 28.0, 40.0);
+            });
+        });
+    });
+}
+
+#[derive(Event, Default)]
+struct Pause;
+
+#[derive(Event, Default)]
+struct Resume;
+
+#[derive(Event, Default)]
+struct RestartRun;
+
+#[derive(Event, Default)]
+struct QuitToMenu;
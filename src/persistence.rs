@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::{WindowFocused, WindowOccluded};
+use bevy_pkv::PkvStore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::GameState;
+use crate::difficulty::DifficultySetting;
+use crate::patterns::PatternSetting;
+use crate::scoring::ScoringMode;
+
+const CHECKPOINT_INTERVAL: f32 = 15.0;
+
+const TOAST_DURATION: f32 = 4.0;
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(HighScore::default())
+        .add_event::<SaveFailed>()
+        .add_systems(OnEnter(GameState::GameOver), flush_high_score)
+        .add_systems(Update, flush_high_score.run_if(in_state(GameState::InProgress).and_then(checkpoint_due)))
+        .add_systems(Last, flush_high_score.run_if(on_event::<AppExit>().or_else(window_hidden)))
+        .add_systems(Update, (show_save_failures.run_if(on_event::<SaveFailed>()), expire_toasts));
+}
+
+/// Where high scores, settings and replays are kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Persistence {
+    /// In a [`PkvStore`], which is at
+    ///   (macOS desktop) ~/Library/Application\ Support/<organization>.<application>/bevy_pkv.redb
+    ///   (macOS Chrome)  ~/Library/Application\ Support/Google/Chrome/Default/Local\ Storage/leveldb/
+    ///       clear local storage in Developer Tools > Application > Local Storage
+    Pkv { organization: String, application: String },
+    /// Only in memory, for as long as the app runs, e.g. in tests.
+    Memory,
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Persistence::Pkv {
+            organization: "awwsmm".into(),
+            application: "flappy-bevy".into(),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub enum Store {
+    Pkv(Box<PkvStore>),
+    /// Values as RON.
+    Memory(HashMap<String, String>),
+}
+
+impl Store {
+    pub fn new(persistence: &Persistence) -> Self {
+        match persistence {
+            Persistence::Pkv { organization, application } => Store::Pkv(Box::new(PkvStore::new(organization, application))),
+            Persistence::Memory => Store::Memory(HashMap::new()),
+        }
+    }
+}
+
+/// Reads and writes persisted values, reporting failed writes with [`SaveFailed`] rather than panicking.
+#[derive(SystemParam)]
+pub struct Storage<'w> {
+    store: ResMut<'w, Store>,
+    failures: EventWriter<'w, SaveFailed>,
+}
+
+impl Storage<'_> {
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        match &*self.store {
+            Store::Pkv(pkv) => pkv.get(key).ok(),
+            Store::Memory(values) => ron::de::from_str(values.get(key)?).ok(),
+        }
+    }
+
+    /// Returns whether the value was written.
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> bool {
+        let written = match &mut *self.store {
+            Store::Pkv(pkv) => pkv.set(key, value).map_err(|err| err.to_string()),
+            Store::Memory(values) => ron::ser::to_string(value).map(|value| {
+                values.insert(key.into(), value);
+            }).map_err(|err| err.to_string()),
+        };
+
+        match written {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("failed to store {}: {}", key, err);
+                self.failures.send(SaveFailed(key.into()));
+                false
+            }
+        }
+    }
+}
+
+/// Sent with the key of a value which couldn't be written.
+#[derive(Event)]
+pub struct SaveFailed(pub String);
+
+/// The all-time high score, kept in memory during a run and only written to storage when it has changed.
+#[derive(Resource, Default)]
+pub struct HighScore {
+    pub best: u64,
+    /// What's currently in storage.
+    pub stored: u64,
+}
+
+pub fn flush_high_score(
+    mut high_score: ResMut<HighScore>,
+    scoring: Res<ScoringMode>,
+    difficulty: Res<DifficultySetting>,
+    pattern: Res<PatternSetting>,
+    mut storage: Storage,
+) {
+    if high_score.best != high_score.stored && storage.set(&scoring.high_score_key(difficulty.0, &pattern.0), &high_score.best) {
+        high_score.stored = high_score.best;
+    }
+}
+
+pub struct Checkpoint(Timer);
+
+impl Default for Checkpoint {
+    fn default() -> Self {
+        Self(Timer::from_seconds(CHECKPOINT_INTERVAL, TimerMode::Repeating))
+    }
+}
+
+pub fn checkpoint_due(
+    time: Res<Time>,
+    mut checkpoint: Local<Checkpoint>,
+) -> bool {
+    checkpoint.0.tick(time.delta()).just_finished()
+}
+
+// on the web, switching tabs or minimizing the browser is the last chance to save before the page may be discarded
+pub fn window_hidden(
+    mut focused: EventReader<WindowFocused>,
+    mut occluded: EventReader<WindowOccluded>,
+) -> bool {
+    let unfocused = focused.read().any(|event| !event.focused);
+    let hidden = occluded.read().any(|event| event.occluded);
+    unfocused || hidden
+}
+
+#[derive(Component)]
+struct Toast(Timer);
+
+fn show_save_failures(
+    mut commands: Commands,
+    mut failures: EventReader<SaveFailed>,
+) {
+    let mut keys: Vec<&str> = failures.read().map(|failure| failure.0.as_str()).collect();
+    keys.sort();
+    keys.dedup();
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                left: Val::Px(20.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.75).into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+        Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once))
+    )).with_children(|parent| {
+        parent.spawn(
+            TextBundle {
+                text: Text::from_section(
+                    format!("Couldn't save {}", keys.join(", ")),
+                    TextStyle {
+                        color: Color::WHITE,
+                        font_size: 30.0,
+                        ..default()
+                    }
+                ),
+                ..default()
+            }
+        );
+    });
+}
+
+// toasts use real time, as virtual time is paused in menus
+fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in &mut toasts {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::app::AppExit;
+use bevy::math::bounding::{Bounded2d, BoundingCircle, IntersectsVolume};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{GameState, Player, Position, PreviousPosition, Scores};
+use crate::difficulty::Difficulty;
+use crate::persistence::{checkpoint_due, Storage, window_hidden};
+use crate::power_ups::PowerUp;
+use crate::replay::recording;
+use crate::simulation::{Crashes, HoleSpawned, Playfield, reset_rng, Seed, SimulationSet, SpawnWalls, update_player_bounds};
+use crate::tuning::GameTuning;
+
+// Coins are placed in holes, and power-ups between one pair of walls and the next. Where they go is drawn
+// from an RNG of their own, so that adding them didn't change any course.
+
+/// Mixed into the seed, so that pickups are placed independently of the walls.
+const PICKUP_SEED: u64 = 0x5049_434B_5550;
+
+pub fn plugin(app: &mut App) {
+    simulation_plugin(app);
+
+    app
+        .insert_resource(Coins::default())
+        .add_systems(Startup, load_coins)
+        .add_systems(Update, bank_coins.run_if(on_event::<Collected>().and_then(recording)))
+        .add_systems(Update, show_coins.run_if(resource_changed::<Coins>))
+        .add_systems(OnEnter(GameState::GameOver), flush_coins)
+        .add_systems(Update, flush_coins.run_if(in_state(GameState::InProgress).and_then(checkpoint_due)))
+        .add_systems(Last, flush_coins.run_if(on_event::<AppExit>().or_else(window_hidden)));
+}
+
+/// Only what's needed to place and collect pickups, without the currency they're stored as.
+pub fn simulation_plugin(app: &mut App) {
+    app
+        .insert_resource(PickupRng(ChaCha8Rng::seed_from_u64(PICKUP_SEED)))
+        .insert_resource(PickupBonus::default())
+        .add_event::<Collected>()
+        .add_systems(OnEnter(GameState::InProgress), (despawn_all_pickups, reset_pickup_rng.after(reset_rng), reset_bonus))
+        .add_systems(OnEnter(GameState::PreGame), despawn_all_pickups)
+        .add_systems(FixedUpdate, (spawn_pickups.after(SpawnWalls), (move_pickups, collect_pickups, remove_passed_pickups).chain().after(update_player_bounds).before(Crashes)).in_set(SimulationSet::Step));
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PickupKind {
+    Coin,
+    PowerUp(PowerUp),
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub bounds: BoundingCircle,
+}
+
+/// Sent when the player touches a pickup.
+#[derive(Event)]
+pub struct Collected(pub PickupKind);
+
+#[derive(Resource)]
+struct PickupRng(ChaCha8Rng);
+
+fn reset_pickup_rng(
+    seed: Res<Seed>,
+    mut rng: ResMut<PickupRng>,
+) {
+    rng.0 = ChaCha8Rng::seed_from_u64(seed.0 ^ PICKUP_SEED);
+}
+
+/// Points earned from pickups in the current run, on top of the score itself.
+#[derive(Resource, Default)]
+pub struct PickupBonus(pub u64);
+
+fn reset_bonus(mut bonus: ResMut<PickupBonus>) {
+    bonus.0 = 0;
+}
+
+fn despawn_all_pickups(
+    mut commands: Commands,
+    pickups: Query<Entity, With<Pickup>>,
+) {
+    for entity in &pickups {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_pickup(commands: &mut Commands, kind: PickupKind, position: Vec2, tuning: &GameTuning) {
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(position.extend(0.0))),
+        Position(position),
+        PreviousPosition(position),
+        Pickup {
+            kind,
+            bounds: Circle::new(tuning.pickup_radius).bounding_circle(position, 0.0),
+        },
+    ));
+}
+
+fn spawn_pickups(
+    mut commands: Commands,
+    mut holes: EventReader<HoleSpawned>,
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<PickupRng>,
+) {
+    let tuning = tuning.with_difficulty(*difficulty);
+
+    for hole in holes.read() {
+        // halfway to where the next pair of walls will be
+        let between_walls = hole.center + Vec2::new(-tuning.wall_speed * tuning.wall_interval / 2.0, 0.0);
+
+        if rng.0.gen_bool(tuning.coin_chance.clamp(0.0, 1.0) as f64) {
+            // a moving hole would carry its walls into a coin left in it
+            let position = if hole.moving { between_walls } else { hole.center };
+            spawn_pickup(&mut commands, PickupKind::Coin, position, &tuning);
+        }
+
+        if rng.0.gen_bool(tuning.power_up_chance.clamp(0.0, 1.0) as f64) {
+            let power_up = PowerUp::ALL[rng.0.gen_range(0..PowerUp::ALL.len())];
+            spawn_pickup(&mut commands, PickupKind::PowerUp(power_up), between_walls, &tuning);
+        }
+    }
+}
+
+// pickups stay still relative to the walls around them
+fn move_pickups(
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    mut pickups: Query<(&mut Position, &mut Pickup)>,
+) {
+    let dx = tuning.with_difficulty(*difficulty).wall_speed * time.delta_seconds();
+
+    for (mut position, mut pickup) in &mut pickups {
+        position.0.x += dx;
+        pickup.bounds.center = position.0;
+    }
+}
+
+pub fn collect_pickups(
+    mut commands: Commands,
+    player: Query<&Player>,
+    pickups: Query<(Entity, &Pickup)>,
+    tuning: Res<GameTuning>,
+    mut bonus: ResMut<PickupBonus>,
+    mut writer: EventWriter<Collected>,
+) {
+    let player = player.single();
+
+    for (entity, pickup) in &pickups {
+        if pickup.bounds.intersects(&player.body) || pickup.bounds.intersects(&player.head) {
+            bonus.0 += match pickup.kind {
+                PickupKind::Coin => tuning.coin_bonus,
+                PickupKind::PowerUp(_) => tuning.power_up_bonus,
+            };
+            writer.send(Collected(pickup.kind));
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn remove_passed_pickups(
+    mut commands: Commands,
+    pickups: Query<(Entity, &Position), With<Pickup>>,
+    playfield: Res<Playfield>,
+) {
+    for (entity, position) in &pickups {
+        if position.0.x < -playfield.width {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Every coin ever collected, to be spent. Kept in memory during a run and only written to storage when it has changed.
+#[derive(Resource, Default)]
+pub struct Coins {
+    pub total: u64,
+    /// What's currently in storage.
+    pub stored: u64,
+}
+
+fn load_coins(
+    mut coins: ResMut<Coins>,
+    storage: Storage,
+) {
+    let stored = storage.get::<u64>("coins").unwrap_or_default();
+    *coins = Coins { total: stored, stored };
+}
+
+// coins collected while watching a replay were already banked when it was recorded
+fn bank_coins(
+    mut reader: EventReader<Collected>,
+    mut coins: ResMut<Coins>,
+) {
+    coins.total += reader.read().filter(|collected| collected.0 == PickupKind::Coin).count() as u64;
+}
+
+fn show_coins(
+    coins: Res<Coins>,
+    mut text: Query<&mut Text, With<Scores>>,
+) {
+    for mut text in &mut text {
+        text.sections[2].value = format!("\nCoins: {}", coins.total);
+    }
+}
+
+fn flush_coins(
+    mut coins: ResMut<Coins>,
+    mut storage: Storage,
+) {
+    if coins.total != coins.stored && storage.set("coins", &coins.total) {
+        coins.stored = coins.total;
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::pickups::{collect_pickups, Collected, PickupKind};
+use crate::simulation::{Crashes, SimulationSet, update_player_bounds};
+use crate::tuning::GameTuning;
+
+// Power-ups last for a number of simulated seconds, counted in fixed ticks like everything else which
+// can change how a run plays out. Slow motion only changes how fast those ticks pass on screen.
+
+pub fn plugin(app: &mut App) {
+    simulation_plugin(app);
+
+    app
+        .add_systems(Startup, spawn_power_up_timers)
+        .add_systems(Update, (apply_slow_motion, show_power_up_timers).run_if(resource_changed::<ActivePowerUps>));
+}
+
+/// Only what's needed for power-ups to change how the simulation plays out.
+pub fn simulation_plugin(app: &mut App) {
+    app
+        .add_systems(OnEnter(GameState::InProgress), end_power_ups)
+        .add_systems(OnExit(GameState::InProgress), end_power_ups)
+        .add_systems(FixedUpdate, (expire_power_ups.before(update_player_bounds), activate_power_ups.after(collect_pickups).before(Crashes)).in_set(SimulationSet::Step));
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUp {
+    /// Lets the player through one pair of walls instead of crashing into it.
+    Shield,
+    SlowMotion,
+    Shrink,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 3] = [PowerUp::Shield, PowerUp::SlowMotion, PowerUp::Shrink];
+
+    fn duration(self, tuning: &GameTuning) -> f32 {
+        match self {
+            PowerUp::Shield => tuning.shield_duration,
+            PowerUp::SlowMotion => tuning.slow_motion_duration,
+            PowerUp::Shrink => tuning.shrink_duration,
+        }
+    }
+}
+
+impl Display for PowerUp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PowerUp::Shield => write!(f, "Shield"),
+            PowerUp::SlowMotion => write!(f, "Slow Motion"),
+            PowerUp::Shrink => write!(f, "Shrink"),
+        }
+    }
+}
+
+/// The power-ups in effect, in the order they were collected, with the simulated seconds each has left.
+#[derive(Resource, Default)]
+pub struct ActivePowerUps {
+    pub active: Vec<(PowerUp, f32)>,
+    /// The hole whose walls a shield was spent on, which the player may finish passing through.
+    pub shielded_hole: Option<u32>,
+}
+
+impl ActivePowerUps {
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.active.iter().any(|(active, _)| *active == power_up)
+    }
+
+    /// Starts a power-up, or restarts it if it's already in effect.
+    fn start(&mut self, power_up: PowerUp, duration: f32) {
+        self.end(power_up);
+        self.active.push((power_up, duration));
+    }
+
+    /// Ends a power-up early, returning whether it was in effect.
+    pub fn end(&mut self, power_up: PowerUp) -> bool {
+        let before = self.active.len();
+        self.active.retain(|(active, _)| *active != power_up);
+        self.active.len() != before
+    }
+
+    /// How large the player is, relative to its usual size.
+    pub fn player_scale(&self, tuning: &GameTuning) -> f32 {
+        if self.is_active(PowerUp::Shrink) { tuning.shrink_scale } else { 1.0 }
+    }
+}
+
+fn end_power_ups(mut power_ups: ResMut<ActivePowerUps>) {
+    *power_ups = ActivePowerUps::default();
+}
+
+fn expire_power_ups(
+    time: Res<Time>,
+    mut power_ups: ResMut<ActivePowerUps>,
+) {
+    let dt = time.delta_seconds();
+
+    for (_, remaining) in &mut power_ups.active {
+        *remaining -= dt;
+    }
+    power_ups.active.retain(|(_, remaining)| *remaining > 0.0);
+}
+
+fn activate_power_ups(
+    mut reader: EventReader<Collected>,
+    tuning: Res<GameTuning>,
+    mut power_ups: ResMut<ActivePowerUps>,
+) {
+    for collected in reader.read() {
+        if let PickupKind::PowerUp(power_up) = collected.0 {
+            power_ups.start(power_up, power_up.duration(&tuning));
+        }
+    }
+}
+
+fn apply_slow_motion(
+    power_ups: Res<ActivePowerUps>,
+    tuning: Res<GameTuning>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let speed = if power_ups.is_active(PowerUp::SlowMotion) { tuning.slow_motion_speed } else { 1.0 };
+
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+#[derive(Component)]
+struct PowerUpTimers;
+
+fn spawn_power_up_timers(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    color: Color::BLACK,
+                    font_size: 40.0,
+                    ..default()
+                }
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                // below the pause button, clear of the toast for failed saves
+                top: Val::Px(120.0),
+                right: Val::Px(20.0),
+                ..default()
+            },
+            ..default()
+        },
+        PowerUpTimers
+    ));
+}
+
+fn show_power_up_timers(
+    power_ups: Res<ActivePowerUps>,
+    mut text: Query<&mut Text, With<PowerUpTimers>>,
+) {
+    let timers: Vec<String> = power_ups.active.iter()
+        .map(|(power_up, remaining)| format!("{}: {:.1}", power_up, remaining))
+        .collect();
+
+    for mut text in &mut text {
+        text.sections[0].value = timers.join("\n");
+    }
+}
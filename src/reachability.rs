@@ -0,0 +1,377 @@
+use serde::Deserialize;
+
+use crate::WallSide;
+use crate::collision::{FlapAnimation, PlayerMasks, PlayerShape};
+use crate::generator::Hole;
+use crate::simulation::{MotionKind, player_x, Playfield, wall_spawn_x};
+use crate::tuning::GameTuning;
+
+// Nothing about how holes are generated guarantees that the player can get from one to the next: a hole can
+// be most of the playfield's height from the last, with too little time between the walls to climb or fall
+// that far. Reachability works out every height the player could be at, tick by tick, from when it reaches
+// one pair of walls until it has cleared the next, the same way the simulation moves it, and the same way
+// moving walls move.
+//
+// The player is taken to be its full size, and to collide with what it does in a run: its head and body
+// circles, or the solid pixels of whichever frame is shown that many ticks after a flap. Either way, it's
+// taken to fill the box around them, and to be alongside a wall whenever any of that box is. That can only
+// make holes harder to get through than they are, so holes are never moved for being out of reach when
+// they aren't, though some which can only just be reached may be moved closer.
+
+/// What's done with a hole the player can't get to from the previous one.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum UnreachableHoles {
+    /// It's moved toward the previous hole, only as far as it takes to be reachable.
+    #[default]
+    Move,
+    /// It's left where it is, with a warning.
+    Report,
+}
+
+/// A hole as the player meets it: where it was placed, and how its walls move from there.
+#[derive(Clone, Copy)]
+pub struct Opening {
+    pub hole: Hole,
+    pub motion: Option<MotionKind>,
+}
+
+/// How the player can move relative to the walls.
+pub struct Reachability {
+    tuning: GameTuning,
+    dt: f32,
+    /// How far the walls move toward the player each tick.
+    step: f32,
+    /// How far behind one wall the next one is.
+    spacing: f32,
+    /// How old the first walls are when the front of the player reaches them, in seconds.
+    first_age: f32,
+    /// How far the player extends below and above its position, indexed by ticks since the last flap, and
+    /// how wide it is at its widest.
+    extents: Vec<(f32, f32)>,
+    width: f32,
+    /// The lowest the player can be without hitting the ground, and the highest it can flap from.
+    ground: f32,
+    ceiling: f32,
+    /// No flight lasts longer than this many ticks without a flap before reaching the ground.
+    max_ticks_since_flap: usize,
+}
+
+/// The heights the player could be at, as ranges which don't overlap.
+type Heights = Vec<(f32, f32)>;
+
+impl Reachability {
+    /// The tuning should already have the run's difficulty applied.
+    pub fn new(tuning: &GameTuning, playfield: &Playfield, masks: &PlayerMasks, dt: f32) -> Self {
+        let radius = tuning.player_radius;
+
+        let ground = -playfield.height / 2.0 + 2.0 * radius;
+        let ceiling = playfield.height / 2.0;
+
+        // the fastest fall is from the top of a flap at the ceiling all the way to the ground
+        let fall = (tuning.impulse * tuning.impulse - 2.0 * tuning.gravity * (ceiling - ground)).sqrt();
+        let max_ticks_since_flap = ((tuning.impulse + fall) / (-tuning.gravity * dt)).ceil() as usize + 1;
+
+        // the animation is timed the way the simulation times it, a tick at a time from the flap
+        let mut animation = FlapAnimation(0.0);
+        let bounds: Vec<_> = (0..=max_ticks_since_flap)
+            .map(|_| {
+                let bounds = PlayerShape::new(tuning, masks, animation, 1.0).bounds();
+                animation.0 += dt;
+                bounds
+            })
+            .collect();
+
+        let front = bounds.iter().map(|bounds| bounds.max.x).fold(f32::NEG_INFINITY, f32::max);
+        let back = bounds.iter().map(|bounds| bounds.min.x).fold(f32::INFINITY, f32::min);
+        let speed = tuning.wall_speed.abs();
+
+        Self {
+            tuning: tuning.clone(),
+            dt,
+            step: speed * dt,
+            spacing: speed * tuning.wall_interval,
+            first_age: (wall_spawn_x(playfield, tuning) - player_x(playfield, tuning) - front) / speed,
+            extents: bounds.iter().map(|bounds| (-bounds.min.y, bounds.max.y)).collect(),
+            width: front - back,
+            ground,
+            ceiling,
+            max_ticks_since_flap,
+        }
+    }
+
+    /// The edges of the walls around the hole, bottom then top, once they're `age` seconds old.
+    fn edges(&self, opening: Opening, age: f32) -> (f32, f32) {
+        let Hole { height, size } = opening.hole;
+        let offset = |side| opening.motion.map_or(0.0, |motion| motion.offset(side, age, &self.tuning));
+
+        (height - size / 2.0 + offset(WallSide::Bottom), height + size / 2.0 + offset(WallSide::Top))
+    }
+
+    /// How far the player moves up on a tick, this many ticks after the one it flapped on.
+    fn rise(&self, ticks_since_flap: usize) -> f32 {
+        (self.tuning.impulse + (ticks_since_flap + 1) as f32 * self.tuning.gravity * self.dt) * self.dt
+    }
+
+    /// Whether the player can get through `to` after getting through `from`, assuming it could have come
+    /// through `from` at any height and speed.
+    pub fn can_reach(&self, from: Opening, to: Opening) -> bool {
+        self.flies_through(from, Some(to))
+    }
+
+    /// Whether the player can get through the hole at all, wherever it is, coming at it at any height and
+    /// speed. Holes too small for the player to stay within while alongside their walls can't be.
+    pub fn can_pass(&self, opening: Opening) -> bool {
+        self.flies_through(opening, None)
+    }
+
+    fn flies_through(&self, first: Opening, second: Option<Opening>) -> bool {
+        // walls move by `travelled` from when the front of the player meets the first wall; the player is
+        // alongside each wall for as long as it takes to move its own width and the wall's
+        let alongside = self.tuning.wall_width + self.width;
+        let end = if second.is_some() { self.spacing + alongside } else { alongside };
+
+        // the heights the player can be at, this many ticks after a flap, between walls with these edges
+        let limits = |ticks: usize, edges: &[(f32, f32)]| {
+            let (below, above) = self.extents[ticks];
+            edges.iter().fold((self.ground, f32::INFINITY), |(low, high), &(bottom, top)| (low.max(bottom + below), high.min(top - above)))
+        };
+
+        // indexed by ticks since the last flap, which is all that the player's speed and frame depend on
+        let first_edges = self.edges(first, self.first_age);
+        let mut heights: Vec<Heights> = (0..=self.max_ticks_since_flap).map(|ticks| vec![limits(ticks, &[first_edges])]).collect();
+        let mut flapped = Heights::new();
+        let mut edges = Vec::with_capacity(2);
+        let mut ticks = 0;
+        let mut travelled = 0.0;
+
+        while travelled < end {
+            ticks += 1;
+            travelled = ticks as f32 * self.step;
+            let age = self.first_age + ticks as f32 * self.dt;
+
+            edges.clear();
+            if travelled < alongside {
+                edges.push(self.edges(first, age));
+            }
+            if let Some(second) = second.filter(|_| travelled >= self.spacing) {
+                edges.push(self.edges(second, age - self.tuning.wall_interval));
+            }
+
+            // a flap from anywhere below the ceiling starts the flight over...
+            flapped.clear();
+            for ranges in &heights {
+                flapped.extend(ranges.iter().filter(|(low, _)| *low < self.ceiling).map(|&(low, high)| (low, high.min(self.ceiling))));
+            }
+            merge(&mut flapped);
+
+            // ...and otherwise it goes on falling, until it's falling faster than any flight could be
+            heights.rotate_right(1);
+            std::mem::swap(&mut heights[0], &mut flapped);
+
+            for (ticks, ranges) in heights.iter_mut().enumerate().filter(|(_, ranges)| !ranges.is_empty()) {
+                let rise = self.rise(ticks);
+                let limits = limits(ticks, &edges);
+                for range in ranges.iter_mut() {
+                    *range = ((range.0 + rise).max(limits.0), (range.1 + rise).min(limits.1));
+                }
+                ranges.retain(|(low, high)| low <= high);
+            }
+
+            if heights.iter().all(Vec::is_empty) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// `to`, moved toward `from` just far enough for the player to be able to reach it, without going
+    /// further than `limit` from the middle of the playfield. It's None if no height is reachable.
+    pub fn move_within_reach(&self, from: Opening, to: Opening, limit: f32) -> Option<Opening> {
+        let toward = from.hole.height.clamp(-limit, limit);
+        let moved = |fraction: f32| Opening { hole: Hole { height: to.hole.height + (toward - to.hole.height) * fraction, ..to.hole }, ..to };
+
+        if !self.can_reach(from, moved(1.0)) {
+            return None;
+        }
+
+        // holes nearer the previous one are easier to reach, so the closest reachable one can be bisected for
+        let (mut unreachable, mut reachable) = (0.0, 1.0);
+        for _ in 0..10 {
+            let fraction = (unreachable + reachable) / 2.0;
+            if self.can_reach(from, moved(fraction)) {
+                reachable = fraction;
+            } else {
+                unreachable = fraction;
+            }
+        }
+
+        Some(moved(reachable))
+    }
+}
+
+/// Sorts ranges, and merges those which overlap.
+fn merge(ranges: &mut Heights) {
+    ranges.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut merged = 0;
+    for i in 1..ranges.len() {
+        if ranges[i].0 <= ranges[merged].1 {
+            ranges[merged].1 = ranges[merged].1.max(ranges[i].1);
+        } else {
+            merged += 1;
+            ranges[merged] = ranges[i];
+        }
+    }
+    ranges.truncate(merged + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::collision::PlayerMasks;
+    use crate::difficulty::Difficulty;
+    use crate::generator::{AuthoredPatterns, Hole, WallPattern};
+    use crate::headless::read_player_masks;
+    use crate::simulation::{DEFAULT_TICK_RATE, MotionKind, place_next_hole, Playfield, PreviousHole};
+    use crate::tuning::GameTuning;
+
+    use super::{Opening, Reachability, UnreachableHoles};
+
+    // far enough into each course for every kind of wall to have started moving
+    const SEEDS: u64 = 32;
+    const HOLES: u32 = 40;
+    const DIFFICULTIES: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Insane];
+
+    fn dt() -> f32 {
+        1.0 / DEFAULT_TICK_RATE as f32
+    }
+
+    fn still(height: f32, size: f32) -> Opening {
+        Opening { hole: Hole { height, size }, motion: None }
+    }
+
+    /// The first holes of a course, placed as they are in a run.
+    fn course(tuning: &GameTuning, masks: &PlayerMasks, pattern: &WallPattern, seed: u64) -> Vec<Opening> {
+        let playfield = Playfield::default();
+        let mut generator = pattern.generator(&AuthoredPatterns::default());
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut previous_hole = PreviousHole::default();
+
+        (0..HOLES)
+            .map(|_| {
+                let (hole, motion) = place_next_hole(&mut previous_hole, generator.as_mut(), &mut rng, tuning, &playfield, masks, dt());
+                Opening { hole, motion }
+            })
+            .collect()
+    }
+
+    /// Each hole which can't be reached from the one before it, though it could have been if it were as
+    /// level with it as the playfield allows. Moving those holes is all that's asked of the generator; the
+    /// rest are too small.
+    fn unreachable_holes(difficulties: &[Difficulty], unreachable: UnreachableHoles) -> Vec<String> {
+        let masks = read_player_masks();
+        assert!(matches!(masks, PlayerMasks::Loaded(_)), "bird.png should make masks");
+
+        // each difficulty is swept on a thread of its own
+        let masks = &masks;
+        std::thread::scope(|scope| {
+            let sweeps: Vec<_> = difficulties.iter().map(|&difficulty| scope.spawn(move || sweep(difficulty, unreachable, masks))).collect();
+            sweeps.into_iter().flat_map(|sweep| sweep.join().unwrap()).collect()
+        })
+    }
+
+    fn sweep(difficulty: Difficulty, unreachable: UnreachableHoles, masks: &PlayerMasks) -> Vec<String> {
+        let tuning = GameTuning { unreachable_holes: unreachable, ..GameTuning::default() }.with_difficulty(difficulty);
+        let playfield = Playfield::default();
+        let context = PreviousHole::default().next_context(&tuning, &playfield);
+        let reachability = Reachability::new(&tuning, &playfield, masks, dt());
+        let mut found = Vec::new();
+
+        for pattern in WallPattern::BUILT_IN {
+            for seed in 0..SEEDS {
+                let holes = course(&tuning, masks, &pattern, seed);
+
+                for (index, pair) in holes.windows(2).enumerate() {
+                    let limit = context.height_limit(pair[1].hole.size + 2.0 * pair[1].motion.map_or(0.0, MotionKind::reach));
+                    let level = Opening { hole: Hole { height: pair[0].hole.height.clamp(-limit, limit), ..pair[1].hole }, ..pair[1] };
+                    if !reachability.can_reach(pair[0], pair[1]) && reachability.can_reach(pair[0], level) {
+                        found.push(format!("{} {} seed {} hole {}", difficulty, pattern, seed, index + 1));
+                    }
+                }
+            }
+        }
+
+        found
+    }
+
+    #[test]
+    fn holes_are_moved_within_reach() {
+        let unreachable = unreachable_holes(&DIFFICULTIES, UnreachableHoles::Move);
+        assert!(unreachable.is_empty(), "unreachable holes: {:?}", unreachable);
+    }
+
+    #[test]
+    fn unreachable_holes_are_left_to_be_reported() {
+        assert!(!unreachable_holes(&[Difficulty::Insane], UnreachableHoles::Report).is_empty());
+    }
+
+    #[test]
+    fn holes_level_with_the_last_can_be_reached() {
+        let tuning = GameTuning::default();
+
+        for masks in [read_player_masks(), PlayerMasks::Unavailable] {
+            let reachability = Reachability::new(&tuning, &Playfield::default(), &masks, dt());
+            let hole = still(0.0, tuning.player_radius * tuning.hole_scale * tuning.initial_min_hole);
+
+            assert!(reachability.can_pass(hole));
+            assert!(reachability.can_reach(hole, hole));
+        }
+    }
+
+    #[test]
+    fn holes_smaller_than_a_flap_cant_be_passed() {
+        let tuning = GameTuning::default();
+
+        for masks in [read_player_masks(), PlayerMasks::Unavailable] {
+            let reachability = Reachability::new(&tuning, &Playfield::default(), &masks, dt());
+            let hole = still(0.0, 4.0 * tuning.player_radius);
+
+            assert!(!reachability.can_pass(hole));
+            assert!(!reachability.can_reach(hole, hole));
+        }
+    }
+
+    #[test]
+    fn walls_still_sliding_into_place_leave_room_to_pass() {
+        let tuning = GameTuning { wall_slide_time: 60.0, ..GameTuning::default() };
+        let playfield = Playfield::default();
+        let reachability = Reachability::new(&tuning, &playfield, &read_player_masks(), dt());
+
+        let closed = still(0.0, 4.0 * tuning.player_radius);
+        let sliding = Opening { motion: Some(MotionKind::Sliding { distance: playfield.height / 2.0 }), ..closed };
+
+        assert!(!reachability.can_pass(closed));
+        assert!(reachability.can_pass(sliding));
+    }
+
+    #[test]
+    fn holes_far_apart_are_out_of_reach_of_close_walls() {
+        let tuning = GameTuning { wall_interval: 0.75, ..GameTuning::default() };
+        let playfield = Playfield::default();
+        let reachability = Reachability::new(&tuning, &playfield, &read_player_masks(), dt());
+        let size = tuning.player_radius * tuning.hole_scale * 1.5;
+        let low = still(-playfield.height / 4.0, size);
+        let high = still(playfield.height / 4.0, size);
+
+        assert!(reachability.can_pass(low) && reachability.can_pass(high));
+        assert!(!reachability.can_reach(low, high));
+        assert!(!reachability.can_reach(high, low));
+
+        let moved = reachability.move_within_reach(low, high, playfield.height / 2.0).unwrap();
+        assert!(moved.hole.height > low.hole.height && moved.hole.height < high.hole.height);
+        assert!(reachability.can_reach(low, moved));
+    }
+}
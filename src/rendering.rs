@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use bevy::render::camera::{ScalingMode, Viewport};
+use bevy::transform::TransformSystem;
+use bevy::window::PrimaryWindow;
+
+use crate::{GameState, Player, Position, PreviousPosition, Wall, WallSide};
+use crate::collision::{BIRD_PATH, FlapAnimation, FRAME_COUNT, FRAME_SIZE, PLAYER_SPRITE_SCALE};
+use crate::ghost::Ghost;
+use crate::pickups::{Pickup, PickupKind};
+use crate::power_ups::{ActivePowerUps, PowerUp};
+use crate::simulation::Playfield;
+use crate::tuning::GameTuning;
+
+// Rendering is an optional layer on top of the simulation: it attaches sprites to simulated
+// entities as they appear, and fits the playfield into the window, letterboxed.
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_systems(Startup, spawn_background)
+        .add_systems(Update, fit_camera)
+        .add_systems(Update, (attach_player_sprite, attach_ghost_sprites, attach_wall_sprites, attach_pickup_sprites))
+        .add_systems(Update, show_ghosts.after(attach_ghost_sprites))
+        .add_systems(Update, scale_player_sprite.after(attach_player_sprite).run_if(resource_changed::<ActivePowerUps>))
+        .add_systems(Update, show_flap_frame.run_if(resource_changed::<FlapAnimation>))
+        .add_systems(PostUpdate, interpolate_positions.before(TransformSystem::TransformPropagate));
+}
+
+// fixed ticks don't line up with frames, so entities are drawn part way between their last two positions
+fn interpolate_positions(
+    time: Res<Time<Fixed>>,
+    state: Res<State<GameState>>,
+    mut query: Query<(&Position, &PreviousPosition, &mut Transform)>,
+) {
+    let alpha = match state.get() {
+        GameState::InProgress => time.overstep_fraction(),
+        _ => 1.0,
+    };
+
+    for (position, previous, mut transform) in &mut query {
+        transform.translation = previous.0.lerp(position.0, alpha).extend(transform.translation.z);
+    }
+}
+
+fn spawn_background(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+) {
+    commands.spawn(
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(Vec2::new(playfield.width, playfield.height)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, -1.0),
+            ..default()
+        }
+    );
+}
+
+// the camera always shows exactly the playfield, in the largest viewport with the same aspect ratio which fits the window
+fn fit_camera(
+    playfield: Res<Playfield>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<(&mut Camera, &mut OrthographicProjection)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    let window_size = Vec2::new(window.physical_width() as f32, window.physical_height() as f32);
+    if window_size.min_element() <= 0.0 {
+        return;
+    }
+
+    let playfield_size = Vec2::new(playfield.width, playfield.height);
+    let scale = (window_size / playfield_size).min_element();
+    let viewport_size = (playfield_size * scale).floor().max(Vec2::ONE);
+    let viewport_position = ((window_size - viewport_size) / 2.0).floor();
+
+    for (mut camera, mut projection) in &mut cameras {
+        let fitted = camera.viewport.as_ref().is_some_and(|viewport| {
+            viewport.physical_size == viewport_size.as_uvec2() && viewport.physical_position == viewport_position.as_uvec2()
+        });
+
+        if !fitted {
+            camera.viewport = Some(Viewport {
+                physical_position: viewport_position.as_uvec2(),
+                physical_size: viewport_size.as_uvec2(),
+                ..default()
+            });
+        }
+
+        if !matches!(projection.scaling_mode, ScalingMode::Fixed { width, height } if width == playfield.width && height == playfield.height) {
+            projection.scaling_mode = ScalingMode::Fixed {
+                width: playfield.width,
+                height: playfield.height,
+            };
+        }
+    }
+}
+
+fn bird_sprite(
+    color: Color,
+    asset_server: &AssetServer,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+) -> (Sprite, Handle<Image>, TextureAtlas, VisibilityBundle) {
+    let texture: Handle<Image> = asset_server.load(BIRD_PATH);
+    let layout = TextureAtlasLayout::from_grid(Vec2::splat(FRAME_SIZE as f32), FRAME_COUNT as usize, 1, None, None);
+    let texture_atlas_layout = texture_atlas_layouts.add(layout);
+
+    (
+        Sprite {
+            color,
+            ..default()
+        },
+        texture,
+        TextureAtlas {
+            layout: texture_atlas_layout,
+            index: 0,
+        },
+        VisibilityBundle::default(),
+    )
+}
+
+fn attach_player_sprite(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut player: Query<(Entity, &mut Transform), Added<Player>>,
+) {
+    for (entity, mut transform) in &mut player {
+        transform.scale = Vec3::splat(PLAYER_SPRITE_SCALE);
+        commands.entity(entity).insert(bird_sprite(Color::WHITE, &asset_server, &mut texture_atlas_layouts));
+    }
+}
+
+/// How opaque a ghost is drawn, before it fades out.
+const GHOST_ALPHA: f32 = 0.4;
+
+fn attach_ghost_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut ghosts: Query<(Entity, &mut Transform), Added<Ghost>>,
+) {
+    for (entity, mut transform) in &mut ghosts {
+        transform.scale = Vec3::splat(PLAYER_SPRITE_SCALE);
+        commands.entity(entity).insert(bird_sprite(Color::rgba(1.0, 1.0, 1.0, GHOST_ALPHA), &asset_server, &mut texture_atlas_layouts));
+    }
+}
+
+fn show_ghosts(
+    mut ghosts: Query<(&Ghost, &mut TextureAtlas, &mut Sprite)>,
+) {
+    for (ghost, mut atlas, mut sprite) in &mut ghosts {
+        atlas.index = ghost.animation.frame();
+        sprite.color.set_a(GHOST_ALPHA * ghost.opacity());
+    }
+}
+
+fn scale_player_sprite(
+    power_ups: Res<ActivePowerUps>,
+    tuning: Res<GameTuning>,
+    mut player: Query<&mut Transform, With<Player>>,
+) {
+    for mut transform in &mut player {
+        transform.scale = Vec3::splat(PLAYER_SPRITE_SCALE * power_ups.player_scale(&tuning));
+    }
+}
+
+fn attach_wall_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    tuning: Res<GameTuning>,
+    walls: Query<(Entity, &Wall), Added<Wall>>,
+) {
+    if walls.is_empty() {
+        return;
+    }
+
+    let texture = asset_server.load("wall.png");
+    let layout = TextureAtlasLayout::from_grid(Vec2::new(128.0, 128.0), 6, 1, None, None);
+    let texture_atlas_layout = texture_atlas_layouts.add(layout);
+
+    for (entity, wall) in walls.iter() {
+        // the wall's transform is at the center of the tile bordering the hole
+        let (cap_index, direction) = match wall.side {
+            WallSide::Top => (4, 1.0),
+            WallSide::Bottom => (5, -1.0),
+        };
+
+        commands.entity(entity).insert(VisibilityBundle::default()).with_children(|parent| {
+            parent.spawn(
+                SpriteSheetBundle {
+                    texture: texture.clone(),
+                    atlas: TextureAtlas {
+                        layout: texture_atlas_layout.clone(),
+                        index: cap_index,
+                    },
+                    ..default()
+                }
+            );
+
+            // tiled out to the far end of the wall, which may extend past the playfield if the wall moves
+            let mut offset = tuning.tile_size;
+
+            while offset < 2.0 * wall.rectangle.half_size.y {
+                parent.spawn(
+                    SpriteSheetBundle {
+                        texture: texture.clone(),
+                        atlas: TextureAtlas {
+                            layout: texture_atlas_layout.clone(),
+                            index: 3,
+                        },
+                        transform: Transform::from_translation(Vec3::Y * direction * offset),
+                        ..default()
+                    }
+                );
+
+                offset += tuning.tile_size;
+            }
+        });
+    }
+}
+
+fn attach_pickup_sprites(
+    mut commands: Commands,
+    pickups: Query<(Entity, &Pickup), Added<Pickup>>,
+) {
+    for (entity, pickup) in &pickups {
+        let color = match pickup.kind {
+            PickupKind::Coin => Color::GOLD,
+            PickupKind::PowerUp(PowerUp::Shield) => Color::CYAN,
+            PickupKind::PowerUp(PowerUp::SlowMotion) => Color::FUCHSIA,
+            PickupKind::PowerUp(PowerUp::Shrink) => Color::ORANGE_RED,
+        };
+
+        commands.entity(entity).insert((
+            Sprite {
+                color,
+                custom_size: Some(Vec2::splat(2.0 * pickup.bounds.radius())),
+                ..default()
+            },
+            Handle::<Image>::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
+
+// the frame shown is the one the simulation collides with
+fn show_flap_frame(
+    animation: Res<FlapAnimation>,
+    mut player: Query<&mut TextureAtlas, With<Player>>,
+) {
+    for mut atlas in &mut player {
+        let frame = animation.frame();
+        if atlas.index != frame {
+            atlas.index = frame;
+        }
+    }
+}
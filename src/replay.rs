@@ -0,0 +1,359 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::difficulty::Difficulty;
+use crate::generator::WallPattern;
+use crate::persistence::Storage;
+use crate::simulation::{DEFAULT_TICK_RATE, Flap, Seed, SimulationSet, Tick, TickRate};
+
+// A run is fully determined by its difficulty, its wall pattern, its seed and the ticks on which the player flapped, so that's all a replay holds.
+
+const MAX_REPLAYS: usize = 10;
+
+pub fn plugin(app: &mut App) {
+    playback_plugin(app);
+
+    app
+        .insert_resource(Recorder::default())
+        .insert_resource(Replays::default())
+        .add_systems(Startup, load_replays)
+        .add_systems(OnEnter(GameState::InProgress), (start_recording, use_tick_rate).run_if(recording))
+        .add_systems(FixedUpdate, record_flaps.in_set(SimulationSet::Step).run_if(recording))
+        .init_resource::<SaveReplays>()
+        .add_event::<RunRecorded>()
+        .add_systems(OnEnter(GameState::GameOver), finish_run);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    app.add_systems(Update, save_replay_files.run_if(on_event::<RunRecorded>().and_then(resource_equals(SaveReplays(true)))));
+}
+
+/// Only what's needed to play a replay back, without recording or storing anything.
+pub fn playback_plugin(app: &mut App) {
+    app
+        .insert_resource(ReplayMode::default())
+        .add_systems(FixedUpdate, inject_flaps.in_set(SimulationSet::Input).run_if(playing_back));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    /// The fixed tick rate the run was recorded at, in Hz.
+    pub tick_rate: f64,
+    pub difficulty: Difficulty,
+    pub pattern: WallPattern,
+    pub seed: u64,
+    /// The tick on which the run ended.
+    pub ticks: u64,
+    /// The ticks on which the player flapped, in increasing order.
+    pub flaps: Vec<u64>,
+}
+
+const FORMAT_VERSION: u32 = 4;
+
+/// Replays are encoded as `version tick_rate difficulty pattern seed ticks flaps`, where `flaps` is a
+/// comma-separated list of the number of ticks between consecutive flaps, e.g.
+/// `4 64 normal random-walk 42 812 30,41,38`.
+///
+/// Version 3 replays have no `pattern`, and were all played with [`WallPattern::RandomWalk`]. Version 2
+/// replays also have no `difficulty`, and were all played on [`Difficulty::Normal`]. Version 1 replays also
+/// have no `tick_rate`, and were all recorded at [`DEFAULT_TICK_RATE`].
+impl Display for Replay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {} {} {} {} ", FORMAT_VERSION, self.tick_rate, self.difficulty.to_string().to_lowercase(), self.pattern.key(), self.seed, self.ticks)?;
+
+        let mut previous = 0;
+        for (i, flap) in self.flaps.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", flap - previous)?;
+            previous = *flap;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Malformed,
+    UnsupportedVersion(u32),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Malformed => write!(f, "malformed replay"),
+            ReplayError::UnsupportedVersion(version) => write!(f, "unsupported replay version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl FromStr for Replay {
+    type Err = ReplayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.trim().split(' ');
+        let mut next = || fields.next().ok_or(ReplayError::Malformed);
+
+        let version: u32 = next()?.parse().map_err(|_| ReplayError::Malformed)?;
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let tick_rate = match version {
+            1 => DEFAULT_TICK_RATE,
+            _ => next()?.parse().map_err(|_| ReplayError::Malformed)?,
+        };
+
+        let difficulty = match version {
+            1 | 2 => Difficulty::Normal,
+            _ => next()?.parse().map_err(|_| ReplayError::Malformed)?,
+        };
+
+        let pattern = match version {
+            1..=3 => WallPattern::RandomWalk,
+            _ => next()?.parse().map_err(|_| ReplayError::Malformed)?,
+        };
+
+        let seed = next()?.parse().map_err(|_| ReplayError::Malformed)?;
+        let ticks = next()?.parse().map_err(|_| ReplayError::Malformed)?;
+
+        let mut flaps = Vec::new();
+        let mut previous = 0;
+        for delta in next().unwrap_or_default().split(',').filter(|delta| !delta.is_empty()) {
+            let delta: u64 = delta.parse().map_err(|_| ReplayError::Malformed)?;
+            previous += delta;
+            flaps.push(previous);
+        }
+
+        Ok(Self { tick_rate, difficulty, pattern, seed, ticks, flaps })
+    }
+}
+
+/// Whether flaps come from the player, from a replay, or from the autopilot.
+#[derive(Resource, Default)]
+pub enum ReplayMode {
+    #[default]
+    Recording,
+    Playback(Replay),
+    Autopilot,
+}
+
+pub fn recording(mode: Res<ReplayMode>) -> bool {
+    matches!(*mode, ReplayMode::Recording)
+}
+
+pub fn playing_back(mode: Res<ReplayMode>) -> bool {
+    matches!(*mode, ReplayMode::Playback(_))
+}
+
+pub fn autopiloting(mode: Res<ReplayMode>) -> bool {
+    matches!(*mode, ReplayMode::Autopilot)
+}
+
+/// Switches to playback, starting from the next run.
+pub fn play(replay: Replay, mode: &mut ReplayMode, difficulty: &mut Difficulty, pattern: &mut WallPattern, seed: &mut Seed, time: &mut Time<Fixed>) {
+    *difficulty = replay.difficulty;
+    *pattern = replay.pattern.clone();
+    seed.0 = replay.seed;
+    time.set_timestep_hz(replay.tick_rate);
+    *mode = ReplayMode::Playback(replay);
+}
+
+// undoes the tick rate of any replay which was played before this run
+fn use_tick_rate(
+    tick_rate: Res<TickRate>,
+    mut time: ResMut<Time<Fixed>>,
+) {
+    time.set_timestep_hz(tick_rate.0);
+}
+
+fn inject_flaps(
+    mode: Res<ReplayMode>,
+    tick: Res<Tick>,
+    mut writer: EventWriter<Flap>,
+) {
+    if let ReplayMode::Playback(replay) = mode.as_ref() {
+        if replay.flaps.binary_search(&tick.0).is_ok() {
+            writer.send(Flap);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Recorder {
+    flaps: Vec<u64>,
+}
+
+fn start_recording(mut recorder: ResMut<Recorder>) {
+    recorder.flaps.clear();
+}
+
+fn record_flaps(
+    mut reader: EventReader<Flap>,
+    tick: Res<Tick>,
+    mut recorder: ResMut<Recorder>,
+) {
+    if reader.read().count() > 0 && recorder.flaps.last() != Some(&tick.0) {
+        recorder.flaps.push(tick.0);
+    }
+}
+
+/// Sent at game over with the replay of the run which just ended, unless it was a replay or a demo.
+#[derive(Event)]
+pub struct RunRecorded(pub Replay);
+
+/// Whether each recorded run is also written to a file under `replays/` in the working directory. Off unless the
+/// app asks for it, as those files are never pruned.
+#[derive(Resource, Default, PartialEq)]
+pub struct SaveReplays(pub bool);
+
+/// The most recently recorded runs, newest first.
+#[derive(Resource, Default)]
+pub struct Replays(pub Vec<Replay>);
+
+fn load_replays(
+    mut replays: ResMut<Replays>,
+    storage: Storage,
+) {
+    if let Some(encoded) = storage.get::<Vec<String>>("replays") {
+        replays.0 = encoded.iter().filter_map(|replay| replay.parse().ok()).collect();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn finish_run(
+    mut mode: ResMut<ReplayMode>,
+    difficulty: Res<Difficulty>,
+    pattern: Res<WallPattern>,
+    seed: Res<Seed>,
+    tick: Res<Tick>,
+    tick_rate: Res<TickRate>,
+    mut recorder: ResMut<Recorder>,
+    mut replays: ResMut<Replays>,
+    mut storage: Storage,
+    mut writer: EventWriter<RunRecorded>,
+) {
+    if !matches!(*mode, ReplayMode::Recording) {
+        *mode = ReplayMode::Recording;
+        return;
+    }
+
+    let replay = Replay {
+        tick_rate: tick_rate.0,
+        difficulty: *difficulty,
+        pattern: pattern.clone(),
+        seed: seed.0,
+        ticks: tick.0,
+        flaps: std::mem::take(&mut recorder.flaps),
+    };
+
+    writer.send(RunRecorded(replay.clone()));
+    replays.0.insert(0, replay);
+    replays.0.truncate(MAX_REPLAYS);
+
+    let encoded: Vec<String> = replays.0.iter().map(Replay::to_string).collect();
+    storage.set("replays", &encoded);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_replay_files(mut reader: EventReader<RunRecorded>) {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    for RunRecorded(replay) in reader.read() {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let directory = std::path::Path::new("replays");
+        let path = directory.join(format!("{}-{}.replay", replay.seed, timestamp));
+
+        if let Err(err) = std::fs::create_dir_all(directory).and_then(|_| std::fs::write(&path, replay.to_string())) {
+            warn!("failed to save replay to {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::difficulty::Difficulty;
+    use crate::generator::WallPattern;
+    use crate::simulation::DEFAULT_TICK_RATE;
+
+    use super::{Replay, ReplayError};
+
+    fn replay(encoded: &str) -> Replay {
+        encoded.parse().unwrap_or_else(|err| panic!("failed to parse {:?}: {}", encoded, err))
+    }
+
+    #[test]
+    fn replays_are_written_as_the_current_version_and_read_back() {
+        let original = Replay {
+            tick_rate: 60.0,
+            difficulty: Difficulty::Hard,
+            pattern: WallPattern::Authored("gauntlet".into()),
+            seed: 42,
+            ticks: 812,
+            flaps: vec![30, 71, 109],
+        };
+
+        let encoded = original.to_string();
+        assert_eq!(encoded, "4 60 hard authored:gauntlet 42 812 30,41,38");
+        assert_eq!(replay(&encoded), original);
+    }
+
+    #[test]
+    fn replays_without_flaps_are_read_back() {
+        let original = Replay {
+            tick_rate: DEFAULT_TICK_RATE,
+            difficulty: Difficulty::Normal,
+            pattern: WallPattern::RandomWalk,
+            seed: 7,
+            ticks: 100,
+            flaps: vec![],
+        };
+
+        assert_eq!(replay(&original.to_string()), original);
+    }
+
+    #[test]
+    fn version_3_replays_were_all_random_walks() {
+        let replay = replay("3 128 easy 42 812 30,41,38");
+
+        assert_eq!(replay.tick_rate, 128.0);
+        assert_eq!(replay.difficulty, Difficulty::Easy);
+        assert_eq!(replay.pattern, WallPattern::RandomWalk);
+        assert_eq!((replay.seed, replay.ticks, replay.flaps), (42, 812, vec![30, 71, 109]));
+    }
+
+    #[test]
+    fn version_2_replays_were_all_on_normal() {
+        let replay = replay("2 128 42 812 30,41,38");
+
+        assert_eq!(replay.tick_rate, 128.0);
+        assert_eq!(replay.difficulty, Difficulty::Normal);
+        assert_eq!(replay.pattern, WallPattern::RandomWalk);
+        assert_eq!((replay.seed, replay.ticks, replay.flaps), (42, 812, vec![30, 71, 109]));
+    }
+
+    #[test]
+    fn version_1_replays_were_all_at_the_default_tick_rate() {
+        let replay = replay("1 42 812 30,41,38");
+
+        assert_eq!(replay.tick_rate, DEFAULT_TICK_RATE);
+        assert_eq!(replay.difficulty, Difficulty::Normal);
+        assert_eq!(replay.pattern, WallPattern::RandomWalk);
+        assert_eq!((replay.seed, replay.ticks, replay.flaps), (42, 812, vec![30, 71, 109]));
+    }
+
+    #[test]
+    fn unknown_versions_and_malformed_replays_are_rejected() {
+        assert!(matches!("0 42 812 30".parse::<Replay>(), Err(ReplayError::UnsupportedVersion(0))));
+        assert!(matches!("5 64 normal random-walk 42 812 30".parse::<Replay>(), Err(ReplayError::UnsupportedVersion(5))));
+        assert!(matches!("4 64 normal random-walk 42".parse::<Replay>(), Err(ReplayError::Malformed)));
+        assert!(matches!("4 64 normal random-walk 42 812 30,x".parse::<Replay>(), Err(ReplayError::Malformed)));
+        assert!(matches!("".parse::<Replay>(), Err(ReplayError::Malformed)));
+    }
+}
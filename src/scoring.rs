@@ -0,0 +1,81 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::Difficulty;
+use crate::generator::WallPattern;
+use crate::persistence::Storage;
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(ScoringMode::default())
+        .add_event::<CycleScoringMode>()
+        .add_systems(PreStartup, load_scoring_mode)
+        .add_systems(Update, cycle_scoring_mode.run_if(on_event::<CycleScoringMode>()));
+}
+
+/// What a run is scored by. Each mode keeps its own high score and leaderboard, per [`Difficulty`] and
+/// [`WallPattern`].
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScoringMode {
+    /// Seconds survived.
+    #[default]
+    Time,
+    /// Pairs of walls passed.
+    Walls,
+}
+
+impl ScoringMode {
+    fn next(self) -> Self {
+        match self {
+            ScoringMode::Time => ScoringMode::Walls,
+            ScoringMode::Walls => ScoringMode::Time,
+        }
+    }
+
+    // time-based scores keep the keys they had before walls could be scored
+
+    pub fn high_score_key(self, difficulty: Difficulty, pattern: &WallPattern) -> String {
+        pattern.storage_key(&difficulty.storage_key(match self {
+            ScoringMode::Time => "high score",
+            ScoringMode::Walls => "high score (walls)",
+        }))
+    }
+
+    pub fn leaderboard_key(self, difficulty: Difficulty, pattern: &WallPattern) -> String {
+        pattern.storage_key(&difficulty.storage_key(match self {
+            ScoringMode::Time => "leaderboard",
+            ScoringMode::Walls => "leaderboard (walls)",
+        }))
+    }
+}
+
+impl Display for ScoringMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScoringMode::Time => write!(f, "Time"),
+            ScoringMode::Walls => write!(f, "Walls"),
+        }
+    }
+}
+
+fn load_scoring_mode(
+    mut scoring: ResMut<ScoringMode>,
+    storage: Storage,
+) {
+    if let Some(stored) = storage.get::<ScoringMode>("scoring mode") {
+        *scoring = stored;
+    }
+}
+
+#[derive(Event, Default)]
+pub struct CycleScoringMode;
+
+fn cycle_scoring_mode(
+    mut scoring: ResMut<ScoringMode>,
+    mut storage: Storage,
+) {
+    *scoring = scoring.next();
+    storage.set("scoring mode", &*scoring);
+}
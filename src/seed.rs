@@ -0,0 +1,222 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::prelude::*;
+use rand::random;
+use serde::{Deserialize, Serialize};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::{GameMode, GameState, Scores};
+use crate::persistence::Storage;
+use crate::replay::recording;
+use crate::simulation::{RANDOM_SEED, reset_rng, Seed};
+
+pub fn plugin(app: &mut App) {
+    app
+        .insert_resource(SeedSettings::default())
+        .init_resource::<RequestedSeed>()
+        .add_event::<CycleSeedMode>()
+        .add_systems(Startup, load_seed_settings)
+        .add_systems(Update, cycle_seed_mode.run_if(on_event::<CycleSeedMode>()))
+        .add_systems(Update, show_seed.run_if(resource_changed::<Seed>))
+        .add_systems(OnEnter(GameState::InProgress), choose_seed.before(reset_rng).run_if(recording));
+}
+
+/// How the seed for each run is chosen.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum SeedMode {
+    /// The same seed every run, for practicing a course.
+    Fixed,
+    /// One seed per UTC day, shared by all players.
+    Daily,
+    /// A fresh seed every run.
+    Random,
+}
+
+impl SeedMode {
+    fn next(self) -> Self {
+        match self {
+            SeedMode::Fixed => SeedMode::Daily,
+            SeedMode::Daily => SeedMode::Random,
+            SeedMode::Random => SeedMode::Fixed,
+        }
+    }
+}
+
+impl Display for SeedMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeedMode::Fixed => write!(f, "Fixed"),
+            SeedMode::Daily => write!(f, "Daily"),
+            SeedMode::Random => write!(f, "Random"),
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SeedSettings {
+    pub mode: SeedMode,
+    /// The seed used in [`SeedMode::Fixed`].
+    pub fixed: u64,
+}
+
+impl Default for SeedSettings {
+    fn default() -> Self {
+        Self {
+            mode: SeedMode::Fixed,
+            fixed: RANDOM_SEED,
+        }
+    }
+}
+
+impl SeedSettings {
+    pub fn seed(&self) -> u64 {
+        match self.mode {
+            SeedMode::Fixed => self.fixed,
+            SeedMode::Daily => Date::today().seed(),
+            SeedMode::Random => random(),
+        }
+    }
+}
+
+/// A calendar date in UTC.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Self {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Self::from_days_since_epoch((seconds / 86_400) as i64)
+    }
+
+    /// Converts a number of days since 1970-01-01 into a date in the proleptic Gregorian calendar.
+    ///
+    /// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn from_days_since_epoch(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self { year, month, day }
+    }
+
+    /// A seed which is the same for everyone on this date.
+    pub fn seed(&self) -> u64 {
+        // SplitMix64, so that consecutive days get unrelated courses
+        let mut z = (self.year as u64) << 16 | (self.month as u64) << 8 | self.day as u64;
+        z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// A seed to play, e.g. one which was shared, instead of the one in the stored settings. It becomes the fixed seed.
+#[derive(Resource, Default)]
+pub struct RequestedSeed(pub Option<u64>);
+
+fn load_seed_settings(
+    requested: Res<RequestedSeed>,
+    mut settings: ResMut<SeedSettings>,
+    mut seed: ResMut<Seed>,
+    mut storage: Storage,
+) {
+    if let Some(stored) = storage.get::<SeedSettings>("seed settings") {
+        *settings = stored;
+    }
+
+    if let Some(requested) = requested.0 {
+        settings.mode = SeedMode::Fixed;
+        settings.fixed = requested;
+        storage.set("seed settings", &*settings);
+    }
+
+    seed.0 = settings.seed();
+}
+
+#[derive(Event, Default)]
+pub struct CycleSeedMode;
+
+fn cycle_seed_mode(
+    mut settings: ResMut<SeedSettings>,
+    mut seed: ResMut<Seed>,
+    mut storage: Storage,
+) {
+    settings.mode = settings.mode.next();
+    seed.0 = settings.seed();
+    storage.set("seed settings", &*settings);
+}
+
+fn choose_seed(
+    settings: Res<SeedSettings>,
+    mode: Res<GameMode>,
+    mut seed: ResMut<Seed>,
+) {
+    seed.0 = match *mode {
+        GameMode::Classic => settings.seed(),
+        GameMode::DailyChallenge => Date::today().seed(),
+    };
+}
+
+fn show_seed(
+    seed: Res<Seed>,
+    mut text: Query<&mut Text, With<Scores>>,
+) {
+    for mut text in &mut text {
+        text.sections[1].value = format!("\nSeed: {}", seed.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Date;
+
+    fn date(year: i64, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    #[test]
+    fn days_are_counted_from_the_start_of_1970() {
+        assert_eq!(Date::from_days_since_epoch(0), date(1970, 1, 1));
+        assert_eq!(Date::from_days_since_epoch(-1), date(1969, 12, 31));
+    }
+
+    #[test]
+    fn leap_days_are_counted() {
+        assert_eq!(Date::from_days_since_epoch(11_015), date(2000, 2, 28));
+        assert_eq!(Date::from_days_since_epoch(11_016), date(2000, 2, 29));
+        assert_eq!(Date::from_days_since_epoch(11_017), date(2000, 3, 1));
+    }
+
+    #[test]
+    fn years_roll_over_at_the_end_of_december() {
+        assert_eq!(Date::from_days_since_epoch(20_088), date(2024, 12, 31));
+        assert_eq!(Date::from_days_since_epoch(20_089), date(2025, 1, 1));
+    }
+
+    #[test]
+    fn every_player_gets_the_same_seed_on_the_same_day() {
+        let day = Date::from_days_since_epoch(20_744);
+        assert_eq!(day, date(2026, 10, 18));
+
+        // pinned, so that a change to how seeds are derived can't go unnoticed
+        assert_eq!(day.seed(), 16_367_164_553_602_477_410);
+        assert_eq!(day.seed(), date(2026, 10, 18).seed());
+        assert_ne!(day.seed(), Date::from_days_since_epoch(20_745).seed());
+    }
+}
@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+
+use crate::{GameState, handle_button_event, handle_setting_button, spawn_button};
+use crate::input::{Action, Bindings, Rebinding};
+use crate::persistence::Storage;
+
+pub fn plugin(app: &mut App) {
+    app
+        .add_systems(OnEnter(GameState::Settings), settings_screen)
+        .add_systems(OnExit(GameState::Settings), stop_rebinding)
+        .add_systems(Update, (start_rebinding, show_bindings).chain().run_if(in_state(GameState::Settings)))
+        .add_event::<ResetBindings>()
+        .add_systems(Update, handle_setting_button::<ResetBindingsButton, ResetBindings>.run_if(in_state(GameState::Settings)))
+        .add_systems(Update, reset_bindings.run_if(in_state(GameState::Settings).and_then(on_event::<ResetBindings>())))
+        .add_event::<CloseSettings>()
+        .add_systems(Update, handle_button_event::<CloseSettingsButton, CloseSettings, SettingsMenu>.run_if(in_state(GameState::Settings)))
+        .add_systems(Update, close_settings.run_if(in_state(GameState::Settings).and_then(on_event::<CloseSettings>())));
+}
+
+#[derive(Component)]
+struct SettingsMenu;
+
+#[derive(Component)]
+struct RebindButton(Action);
+
+#[derive(Component)]
+struct ResetBindingsButton;
+
+#[derive(Component)]
+struct CloseSettingsButton;
+
+#[derive(Event, Default)]
+struct ResetBindings;
+
+#[derive(Event, Default)]
+struct CloseSettings;
+
+fn binding_label(action: Action, bindings: &Bindings, rebinding: Option<&Rebinding>) -> String {
+    if rebinding.is_some_and(|rebinding| rebinding.0 == action) {
+        return format!("{}: press an input to add or remove it", action);
+    }
+
+    let bound: Vec<String> = bindings.get(action).iter().map(ToString::to_string).collect();
+    if bound.is_empty() {
+        format!("{}: (unbound)", action)
+    } else {
+        format!("{}: {}", action, bound.join(", "))
+    }
+}
+
+fn start_rebinding(
+    mut commands: Commands,
+    mut buttons: Query<(&Interaction, &mut BackgroundColor, &RebindButton), Changed<Interaction>>,
+) {
+    for (interaction, mut color, button) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 1.0).into();
+                commands.insert_resource(Rebinding(button.0));
+            }
+            Interaction::Hovered => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 0.75).into();
+            }
+            Interaction::None => {
+                *color = Color::rgba(0.0, 1.0, 0.0, 0.5).into();
+            }
+        }
+    }
+}
+
+fn show_bindings(
+    bindings: Res<Bindings>,
+    rebinding: Option<Res<Rebinding>>,
+    buttons: Query<(&Children, &RebindButton)>,
+    mut text: Query<&mut Text>,
+) {
+    for (children, button) in &buttons {
+        let label = binding_label(button.0, &bindings, rebinding.as_deref());
+
+        let mut iter = text.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            if text.sections[0].value != label {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+fn reset_bindings(
+    mut bindings: ResMut<Bindings>,
+    mut storage: Storage,
+) {
+    *bindings = Bindings::default();
+    storage.set("bindings", &*bindings);
+}
+
+fn stop_rebinding(mut commands: Commands) {
+    commands.remove_resource::<Rebinding>();
+}
+
+fn close_settings(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::PreGame);
+}
+
+fn settings_screen(
+    mut commands: Commands,
+    bindings: Res<Bindings>,
+) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                left: Val::Px(0.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.50).into(),
+            ..default()
+        },
+        SettingsMenu
+    )).with_children(|parent| {
+        parent.spawn(
+            NodeBundle {
+                style: Style {
+                    border: UiRect::all(Val::Px(3.0)),
+                    width: Val::Percent(60.0),
+                    min_width: Val::Px(550.0),
+                    height: Val::Percent(80.0),
+                    min_height: Val::Px(400.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceAround,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: Color::BLACK.into(),
+                background_color: Color::WHITE.into(),
+                ..default()
+            }
+        ).with_children(|parent| {
+            parent.spawn(
+                TextBundle {
+                    text: Text::from_section(
+                        "Controls",
+                        TextStyle {
+                            color: Color::BLACK,
+                            font_size: 80.0,
+                            ..default()
+                        }
+                    ),
+                    ..default()
+                }
+            );
+
+            for action in Action::ALL {
+                spawn_button(parent, RebindButton(action), binding_label(action, &bindings, None), 15.0, 30.0);
+            }
+
+            spawn_button(parent, ResetBindingsButton, "Reset to Defaults", 12.0, 40.0);
+            spawn_button(parent, CloseSettingsButton, "Back to Menu", 12.0, 40.0);
+        });
+    });
+}
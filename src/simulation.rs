@@ -0,0 +1,699 @@
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::math::bounding::{Aabb2d, Bounded2d};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{GameState, Mass, Player, Position, PreviousPosition, Velocity, Wall, WallSide};
+use crate::collision::{collision_ready, FlapAnimation, PlayerMasks, PlayerShape};
+use crate::difficulty::Difficulty;
+use crate::generator::{AuthoredPatterns, Generator, Hole, HoleContext, reset_generator, WallGenerator, WallPattern};
+use crate::power_ups::{ActivePowerUps, PowerUp};
+use crate::reachability::{Opening, Reachability, UnreachableHoles};
+use crate::tuning::GameTuning;
+
+// The simulation knows nothing about windows, textures, or input devices. It runs under
+// MinimalPlugins, so it can be stepped headlessly; see the rendering module for sprites.
+
+pub const RANDOM_SEED: u64 = 42;
+
+/// Fixed ticks per second. Physics is in units per second, so this only changes how finely runs are
+/// simulated, but a replay can only be reproduced at the rate it was recorded at.
+pub const DEFAULT_TICK_RATE: f64 = 64.0;
+
+pub fn plugin(app: &mut App) {
+    let tick_rate = requested_tick_rate();
+
+    app
+        .insert_resource(TickRate(tick_rate))
+        .insert_resource(Time::<Fixed>::from_hz(tick_rate))
+        .init_resource::<GameTuning>()
+        .init_resource::<Difficulty>()
+        .init_resource::<WallPattern>()
+        .init_resource::<AuthoredPatterns>()
+        .init_resource::<Generator>()
+        .init_resource::<ActivePowerUps>()
+        .insert_resource(Playfield::default())
+        .add_event::<Flap>()
+        .add_event::<Despawn>()
+        .add_event::<HoleSpawned>()
+        .configure_sets(FixedUpdate, (SimulationSet::Input, SimulationSet::Step).chain().run_if(in_state(GameState::InProgress).and_then(no_pending_transition).and_then(collision_ready)))
+        .add_systems(Startup, (spawn_player, reset_player).chain())
+        .add_systems(Update, despawn.run_if(on_event::<Despawn>()))
+        .add_systems(OnEnter(GameState::InProgress), (reset_player, despawn_all_walls, reset_hole_info, reset_rng, reset_generator, reset_tick, reset_wall_timer, reset_walls_cleared))
+        .add_systems(FixedUpdate, remember_positions.before(SimulationSet::Input))
+        .add_systems(FixedUpdate, (flap, gravity, update_player_bounds, move_walls, (hit_ground, hit_wall).chain().in_set(Crashes), cleared_wall, pass_walls, spawn_wall.in_set(SpawnWalls)).chain().in_set(SimulationSet::Step))
+        .add_systems(FixedUpdate, advance_tick.after(SimulationSet::Step).run_if(in_state(GameState::InProgress).and_then(no_pending_transition).and_then(collision_ready)))
+        .insert_resource(Seed(RANDOM_SEED))
+        .insert_resource(RNG(ChaCha8Rng::seed_from_u64(RANDOM_SEED)))
+        .insert_resource(Tick::default())
+        .insert_resource(WallsCleared::default())
+        .insert_resource(WallTimer(Timer::from_seconds(GameTuning::default().wall_interval, TimerMode::Repeating)))
+        .insert_resource(PreviousHole::default());
+}
+
+/// The fixed tick rate in use for recorded runs, in Hz.
+#[derive(Resource)]
+pub struct TickRate(pub f64);
+
+/// Returns the rate passed with `--tick-rate <hz>`, or [`DEFAULT_TICK_RATE`].
+fn requested_tick_rate() -> f64 {
+    std::env::args().skip_while(|arg| arg != "--tick-rate").nth(1)
+        .and_then(|rate| rate.parse().ok())
+        .filter(|rate: &f64| *rate > 0.0)
+        .unwrap_or(DEFAULT_TICK_RATE)
+}
+
+/// Each fixed tick, everything which decides whether the player flaps runs in [`SimulationSet::Input`],
+/// before the simulation is advanced in [`SimulationSet::Step`].
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum SimulationSet {
+    Input,
+    Step,
+}
+
+/// Where walls are spawned within [`SimulationSet::Step`], so that whatever reacts to [`HoleSpawned`] can run after.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct SpawnWalls;
+
+/// The systems which end the run when the player hits the ground or a wall.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Crashes;
+
+/// Once the player has crashed, no more ticks are simulated, even if several fixed ticks run before the
+/// state transition is applied. This keeps runs deterministic regardless of frame rate.
+fn no_pending_transition(next_state: Res<NextState<GameState>>) -> bool {
+    next_state.0.is_none()
+}
+
+/// The index of the fixed tick being simulated in the current run. When the player crashes, this is the crash tick.
+#[derive(Resource, Default)]
+pub struct Tick(pub u64);
+
+fn reset_tick(mut tick: ResMut<Tick>) {
+    tick.0 = 0;
+}
+
+fn advance_tick(mut tick: ResMut<Tick>) {
+    tick.0 += 1;
+}
+
+/// The size of the area the game is played in, in world units, centered on the origin.
+///
+/// This is the same on every device, whatever the size of the window, so that runs are comparable.
+#[derive(Resource)]
+pub struct Playfield {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Playfield {
+    fn default() -> Self {
+        Self {
+            width: 1280.0,
+            height: 720.0,
+        }
+    }
+}
+
+/// Sent to make the player flap on the next fixed tick.
+#[derive(Event, Default)]
+pub struct Flap;
+
+#[derive(Event)]
+pub struct Despawn(pub Entity);
+
+/// Sent when a pair of walls is spawned, with where the hole between them is.
+#[derive(Event)]
+pub struct HoleSpawned {
+    pub center: Vec2,
+    /// Whether the walls move vertically, carrying the hole with them.
+    pub moving: bool,
+}
+
+fn despawn(
+    mut commands: Commands,
+    mut reader: EventReader<Despawn>,
+) {
+    for despawn in reader.read() {
+        commands.entity(despawn.0).despawn_recursive();
+    }
+}
+
+// right away rather than with a Despawn, so that a restarted run doesn't begin with the last run's walls
+pub fn despawn_all_walls(
+    mut commands: Commands,
+    query: Query<Entity, With<Wall>>,
+) {
+    for e in &query {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+fn remember_positions(
+    mut query: Query<(&Position, &mut PreviousPosition)>,
+) {
+    for (position, mut previous) in &mut query {
+        previous.0 = position.0;
+    }
+}
+
+fn spawn_player(mut commands: Commands) {
+    commands.spawn((
+        TransformBundle::default(),
+        Position::default(),
+        PreviousPosition::default(),
+        Mass,
+        Velocity::default(),
+        Player::default(),
+    ));
+}
+
+pub fn reset_player(
+    playfield: Res<Playfield>,
+    tuning: Res<GameTuning>,
+    mut player: Query<(&mut Transform, &mut Position, &mut PreviousPosition, &mut Velocity, &mut Player)>,
+) {
+    let (mut transform, mut position, mut previous, mut velocity, mut player) = player.single_mut();
+
+    let translation = Vec2::new(player_x(&playfield, &tuning), 0.0);
+
+    // teleported, rather than interpolated from wherever the last run ended
+    position.0 = translation;
+    previous.0 = translation;
+    transform.translation = translation.extend(0.0);
+    velocity.0 = Vec2::default();
+    player.body = Circle::new(tuning.player_radius).bounding_circle(translation, 0.0);
+    player.head = Circle::new(tuning.player_radius).bounding_circle(translation, 0.0);
+}
+
+/// Where along x the player flies, which it never leaves.
+pub fn player_x(playfield: &Playfield, tuning: &GameTuning) -> f32 {
+    -playfield.width / 2.0 + 4.0 * tuning.player_radius // fine-tuned
+}
+
+fn gravity(
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    mut query: Query<(&mut Velocity, &mut Position), With<Mass>>,
+) {
+    let tuning = tuning.with_difficulty(*difficulty);
+    let dt = time.delta_seconds();
+
+    for (mut velocity, mut position) in query.iter_mut() {
+        velocity.0.y += tuning.gravity * dt;
+        position.0 += velocity.0 * dt;
+    }
+}
+
+fn flap(
+    mut reader: EventReader<Flap>,
+    mut player: Query<(&mut Velocity, &Position), With<Player>>,
+    playfield: Res<Playfield>,
+    tuning: Res<GameTuning>,
+) {
+    if reader.read().count() == 0 {
+        return;
+    }
+
+    let (mut velocity, position) = player.single_mut();
+    apply_flap(&mut velocity, position, &playfield, &tuning);
+}
+
+/// A flap sets the upward speed, unless the flapper is already above the playfield.
+pub fn apply_flap(velocity: &mut Velocity, position: &Position, playfield: &Playfield, tuning: &GameTuning) {
+    if position.0.y < playfield.height / 2.0 {
+        velocity.0.y = tuning.impulse;
+    }
+}
+
+pub fn update_player_bounds(
+    tuning: Res<GameTuning>,
+    power_ups: Res<ActivePowerUps>,
+    mut player: Query<(&Position, &mut Player)>,
+) {
+    let (position, mut player) = player.single_mut();
+    let scale = power_ups.player_scale(&tuning);
+    player.body = Circle::new(tuning.player_radius * scale).bounding_circle(position.0 + tuning.body_offset * scale, 0.0);
+    player.head = Circle::new(tuning.player_radius * scale).bounding_circle(position.0 + tuning.head_offset * scale, 0.0);
+}
+
+/// The most recently generated hole, which the next one is placed relative to.
+#[derive(Resource, Default)]
+pub struct PreviousHole {
+    pub height: f32,
+    pub size: f32,
+    pub motion: Option<MotionKind>,
+    /// Counts up from 0 each run; this is also the index of the next hole.
+    pub index: u32,
+}
+
+impl PreviousHole {
+    /// What the generator is told when it places the next hole.
+    pub fn next_context<'a>(&self, tuning: &'a GameTuning, playfield: &'a Playfield) -> HoleContext<'a> {
+        HoleContext {
+            index: self.index,
+            previous_height: self.height,
+            tuning,
+            playfield,
+            // not the player's current size, which power-ups can change
+            unit: tuning.player_radius * tuning.hole_scale,
+        }
+    }
+}
+
+fn reset_hole_info(
+    mut previous_hole: ResMut<PreviousHole>
+) {
+    let default = PreviousHole::default();
+    previous_hole.height = default.height;
+    previous_hole.size = default.size;
+    previous_hole.motion = default.motion;
+    previous_hole.index = default.index;
+}
+
+/// The seed used to generate the walls for the next run.
+#[derive(Resource)]
+pub struct Seed(pub u64);
+
+#[derive(Resource)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct RNG(ChaCha8Rng);
+
+pub fn reset_rng(
+    seed: Res<Seed>,
+    mut rng: ResMut<RNG>,
+) {
+    rng.0 = ChaCha8Rng::seed_from_u64(seed.0)
+}
+
+#[derive(Resource)]
+struct WallTimer(Timer);
+
+fn reset_wall_timer(
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    mut timer: ResMut<WallTimer>,
+) {
+    let tuning = tuning.with_difficulty(*difficulty);
+    timer.0 = Timer::from_seconds(tuning.wall_interval, TimerMode::Repeating);
+}
+
+/// How a wall moves vertically as it crosses the playfield, on top of moving along x like every wall.
+#[derive(Component, Clone, Copy)]
+pub struct WallMotion {
+    kind: MotionKind,
+    /// Seconds since the wall was spawned.
+    age: f32,
+    /// How far the wall currently is from where its hole was generated, vertically.
+    offset: f32,
+}
+
+#[derive(Clone, Copy)]
+pub enum MotionKind {
+    /// The hole moves up and down by `amplitude`.
+    Oscillating { amplitude: f32, phase: f32 },
+    /// Both walls move away from the hole by up to `amplitude`, and back.
+    Opening { amplitude: f32, phase: f32 },
+    /// The wall starts `distance` further from the hole, and slides into place.
+    Sliding { distance: f32 },
+}
+
+impl WallMotion {
+    fn new(kind: MotionKind, side: WallSide, tuning: &GameTuning) -> Self {
+        let mut motion = Self { kind, age: 0.0, offset: 0.0 };
+        motion.offset = motion.offset_at(side, tuning);
+        motion
+    }
+
+    fn offset_at(&self, side: WallSide, tuning: &GameTuning) -> f32 {
+        self.kind.offset(side, self.age, tuning)
+    }
+}
+
+/// Where a wall's bounding box will be after `seconds` more of simulated time, for looking ahead.
+pub fn predict_wall_bounds(wall: &Wall, motion: Option<&WallMotion>, seconds: f32, tuning: &GameTuning) -> Aabb2d {
+    let mut delta = Vec2::new(tuning.wall_speed * seconds, 0.0);
+
+    if let Some(motion) = motion {
+        let later = WallMotion { age: motion.age + seconds, ..*motion };
+        delta.y = later.offset_at(wall.side, tuning) - motion.offset;
+    }
+
+    wall.rectangle.aabb_2d(wall.center + delta, 0.0)
+}
+
+impl MotionKind {
+    /// How far a wall on this side of the hole is from where the hole was generated, `age` seconds after
+    /// it was spawned.
+    pub fn offset(self, side: WallSide, age: f32, tuning: &GameTuning) -> f32 {
+        let away_from_hole = match side {
+            WallSide::Top => 1.0,
+            WallSide::Bottom => -1.0,
+        };
+        let cycle = TAU * age / tuning.wall_motion_period;
+
+        match self {
+            MotionKind::Oscillating { amplitude, phase } => amplitude * (phase + cycle).sin(),
+            MotionKind::Opening { amplitude, phase } => away_from_hole * amplitude * (1.0 - (phase + cycle).cos()) / 2.0,
+            MotionKind::Sliding { distance } => {
+                let t = (age / tuning.wall_slide_time).clamp(0.0, 1.0);
+                let eased = t * t * (3.0 - 2.0 * t);
+                away_from_hole * distance * (1.0 - eased)
+            }
+        }
+    }
+
+    /// How much further walls need to extend toward the playfield edge so as never to leave a gap there.
+    pub fn reach(self) -> f32 {
+        match self {
+            MotionKind::Oscillating { amplitude, .. } => amplitude,
+            MotionKind::Opening { .. } | MotionKind::Sliding { .. } => 0.0,
+        }
+    }
+}
+
+/// Walls start moving once enough holes have been generated, each kind later than the last. Early holes
+/// never draw from the RNG for this, so courses start the same as they did before walls could move.
+fn choose_motion(tuning: &GameTuning, hole: u32, hole_size: f32, playfield: &Playfield, rng: &mut ChaCha8Rng) -> Option<MotionKind> {
+    let available = [tuning.oscillating_walls, tuning.opening_walls, tuning.sliding_walls].iter().filter(|walls| hole >= **walls).count();
+    if available == 0 || !rng.gen_bool(tuning.moving_wall_chance.clamp(0.0, 1.0) as f64) {
+        return None;
+    }
+
+    let amplitude = tuning.wall_motion_amplitude * hole_size;
+
+    Some(match rng.gen_range(0..available) {
+        0 => MotionKind::Oscillating { amplitude, phase: rng.gen_range(0.0..TAU) },
+        1 => MotionKind::Opening { amplitude, phase: rng.gen_range(0.0..TAU) },
+        _ => MotionKind::Sliding { distance: playfield.height / 2.0 },
+    })
+}
+
+/// Walls are spawned with a hole size and a hole height `h`, chosen by the run's [`Generator`].
+///
+/// However the hole is chosen, `h` is always clamped to fall within the playfield, even as the walls move, and
+/// is then kept within reach of the previous hole by [`place_next_hole`].
+#[allow(clippy::too_many_arguments)]
+fn spawn_wall(
+    mut commands: Commands,
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    mut timer: ResMut<WallTimer>,
+    playfield: Res<Playfield>,
+    masks: Res<PlayerMasks>,
+    mut previous_hole: ResMut<PreviousHole>,
+    mut generator: ResMut<Generator>,
+    mut rng: ResMut<RNG>,
+    mut holes: EventWriter<HoleSpawned>,
+) {
+    let tuning = tuning.with_difficulty(*difficulty);
+
+    // so that a change to the tuning file is felt without waiting for the next run
+    let interval = Duration::from_secs_f32(tuning.wall_interval);
+    if timer.0.duration() != interval {
+        timer.0.set_duration(interval);
+    }
+
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // (x, y) position is at the center of the rectangle
+    // x increases to the right, y increases to the top
+    //
+    //   +------------------------------------+
+    //   |                     (x2, y2)       |
+    //   |    (x1, y1)             |          |
+    //   |        |           +----|----+     |
+    //   |   +----|----+      |    v    |     |
+    //   |   |    v    |      |    x    |     |
+    //   |   |    x    |      |         |     |
+    //   |   |         |      +---------+     |
+    //   |   +---------+                      |
+    //   |                  x1 < x2, y1 < y2  |
+    //   +------------------------------------+
+
+    // walls are positioned at the tile which borders the hole; the rest of the wall extends to the playfield edge
+
+    fn spawn_bottom_wall(
+        commands: &mut Commands,
+        tuning: &GameTuning,
+        top_left_corner: Vec2,
+        half_playfield_height: f32,
+        hole: u32,
+        motion: Option<MotionKind>,
+    ) {
+        let motion = motion.map(|kind| WallMotion::new(kind, WallSide::Bottom, tuning));
+        let reach = motion.map_or(0.0, |motion| motion.kind.reach());
+        let offset = Vec2::new(0.0, motion.map_or(0.0, |motion| motion.offset));
+
+        let wall_height = half_playfield_height + top_left_corner.y + reach;
+        let position = top_left_corner + offset + Vec2::new(tuning.wall_width / 2.0, -tuning.tile_size / 2.0);
+
+        let mut wall = commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(position.extend(0.0))),
+            Position(position),
+            PreviousPosition(position),
+            Wall {
+                side: WallSide::Bottom,
+                hole,
+                rectangle: Rectangle::new(tuning.wall_width, wall_height),
+                center: top_left_corner + offset + Vec2::new(tuning.wall_width / 2.0, -wall_height / 2.0),
+                bounding_box: Aabb2d::new(Vec2::ZERO, Vec2::ZERO),
+            }
+        ));
+
+        if let Some(motion) = motion {
+            wall.insert(motion);
+        }
+    }
+
+    fn spawn_top_wall(
+        commands: &mut Commands,
+        tuning: &GameTuning,
+        bottom_left_corner: Vec2,
+        half_playfield_height: f32,
+        hole: u32,
+        motion: Option<MotionKind>,
+    ) {
+        let motion = motion.map(|kind| WallMotion::new(kind, WallSide::Top, tuning));
+        let reach = motion.map_or(0.0, |motion| motion.kind.reach());
+        let offset = Vec2::new(0.0, motion.map_or(0.0, |motion| motion.offset));
+
+        let wall_height = half_playfield_height - bottom_left_corner.y + reach;
+        let position = bottom_left_corner + offset + Vec2::new(tuning.wall_width / 2.0, tuning.tile_size / 2.0);
+
+        let mut wall = commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(position.extend(0.0))),
+            Position(position),
+            PreviousPosition(position),
+            Wall {
+                side: WallSide::Top,
+                hole,
+                rectangle: Rectangle::new(tuning.wall_width, wall_height),
+                center: bottom_left_corner + offset + Vec2::new(tuning.wall_width / 2.0, wall_height / 2.0),
+                bounding_box: Aabb2d::new(Vec2::ZERO, Vec2::ZERO),
+            }
+        ));
+
+        if let Some(motion) = motion {
+            wall.insert(motion);
+        }
+    }
+
+    let half_playfield_height = playfield.height / 2.0;
+    let spawn_x = wall_spawn_x(&playfield, &tuning);
+
+    let hole = previous_hole.index;
+    let (next, motion) = place_next_hole(&mut previous_hole, generator.0.as_mut(), &mut rng.0, &tuning, &playfield, &masks, time.delta_seconds());
+    let h = next.height;
+    let half_hole_size = next.size / 2.0;
+
+    let bottom_of_hole = h - half_hole_size;
+    let top_of_hole = h + half_hole_size;
+
+    debug!("hole: {} -> {}", bottom_of_hole, top_of_hole);
+
+    let bottom_left_corner = Vec2::new(spawn_x, top_of_hole);
+    spawn_top_wall(&mut commands, &tuning, bottom_left_corner, half_playfield_height, hole, motion);
+
+    let top_left_corner = Vec2::new(spawn_x, bottom_of_hole);
+    spawn_bottom_wall(&mut commands, &tuning, top_left_corner, half_playfield_height, hole, motion);
+
+    holes.send(HoleSpawned {
+        center: Vec2::new(spawn_x + tuning.wall_width / 2.0, h),
+        moving: motion.is_some(),
+    });
+}
+
+/// Where the leading edge of a pair of walls is when they're spawned, just off the right of the playfield.
+pub fn wall_spawn_x(playfield: &Playfield, tuning: &GameTuning) -> f32 {
+    playfield.width / 2.0 + tuning.wall_width
+}
+
+/// Where the generator puts the next hole, kept within the playfield and, unless the tuning says otherwise,
+/// within reach of the previous hole, and how its walls move. `dt` is the length of a tick, and the masks are
+/// what the player collides with.
+pub fn place_next_hole(
+    previous_hole: &mut PreviousHole,
+    generator: &mut dyn WallGenerator,
+    rng: &mut ChaCha8Rng,
+    tuning: &GameTuning,
+    playfield: &Playfield,
+    masks: &PlayerMasks,
+    dt: f32,
+) -> (Hole, Option<MotionKind>) {
+    let context = previous_hole.next_context(tuning, playfield);
+    let next = generator.next_hole(&context, rng);
+    // the hole should never extend beyond the playfield
+    let size = next.size.min(playfield.height - 2.0 * tuning.hole_margin);
+    let motion = choose_motion(tuning, previous_hole.index, size, playfield, rng);
+    let reach = motion.map_or(0.0, MotionKind::reach);
+
+    let h_limit = context.height_limit(size + 2.0 * reach);
+    let mut hole = Opening { hole: Hole { height: next.height.clamp(-h_limit, h_limit), size }, motion };
+
+    // the first hole is reached from wherever the player starts
+    if previous_hole.index > 0 {
+        let previous = Opening { hole: Hole { height: previous_hole.height, size: previous_hole.size }, motion: previous_hole.motion };
+        let reachability = Reachability::new(tuning, playfield, masks, dt);
+
+        if !reachability.can_reach(previous, hole) {
+            let moved = match tuning.unreachable_holes {
+                // walls which move the hole out of reach wherever it's put are kept still instead
+                UnreachableHoles::Move => reachability.move_within_reach(previous, hole, h_limit).or_else(|| {
+                    let still = Opening { motion: None, ..hole };
+                    hole.motion.and_then(|_| reachability.move_within_reach(previous, still, context.height_limit(size)))
+                }),
+                UnreachableHoles::Report => None,
+            };
+
+            match moved {
+                Some(moved) => {
+                    debug!("hole {} moved from {} to {} to be reachable", previous_hole.index, hole.hole.height, moved.hole.height);
+                    hole = moved;
+                }
+                // no height would do for a hole which is too small to get through at all
+                None if !reachability.can_pass(hole) => warn!("hole {} is too small to get through", previous_hole.index),
+                None => warn!("hole {} can't be reached from the previous one", previous_hole.index),
+            }
+        }
+    }
+
+    previous_hole.height = hole.hole.height;
+    previous_hole.size = hole.hole.size;
+    previous_hole.motion = hole.motion;
+    previous_hole.index += 1;
+
+    (hole.hole, hole.motion)
+}
+
+/// The number of walls (top and bottom pairs) the player has made it past in the current run.
+#[derive(Resource, Default)]
+pub struct WallsCleared(pub u32);
+
+fn reset_walls_cleared(mut cleared: ResMut<WallsCleared>) {
+    cleared.0 = 0;
+}
+
+/// A pair of walls is cleared once the center of the player's body is past its trailing edge.
+/// Holes are passed in order, so the next hole to clear is always the number cleared so far.
+fn pass_walls(
+    player: Query<&Player>,
+    walls: Query<&Wall>,
+    mut cleared: ResMut<WallsCleared>,
+) {
+    let player = player.single();
+
+    for wall in walls.iter() {
+        if wall.hole == cleared.0 && player.body.center.x > wall.bounding_box.max.x {
+            cleared.0 += 1;
+        }
+    }
+}
+
+fn move_walls(
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    difficulty: Res<Difficulty>,
+    mut walls: Query<(&mut Position, &mut Wall, Option<&mut WallMotion>)>,
+) {
+    let tuning = tuning.with_difficulty(*difficulty);
+    let dt = time.delta_seconds();
+
+    for (mut position, mut wall, motion) in walls.iter_mut() {
+        let mut delta = Vec2::new(tuning.wall_speed * dt, 0.0);
+
+        if let Some(mut motion) = motion {
+            motion.age += dt;
+            let offset = motion.offset_at(wall.side, &tuning);
+            delta.y = offset - motion.offset;
+            motion.offset = offset;
+        }
+
+        position.0 += delta;
+        wall.center += delta;
+        wall.bounding_box = wall.rectangle.aabb_2d(wall.center, 0.0);
+    }
+}
+
+fn cleared_wall(
+    entities: Query<(Entity, &Position), With<Wall>>,
+    playfield: Res<Playfield>,
+    mut writer: EventWriter<Despawn>,
+) {
+    for (entity, position) in entities.iter() {
+        if position.0.x < -playfield.width {
+            writer.send(Despawn(entity));
+        }
+    }
+}
+
+fn hit_ground(
+    player: Query<(&Position, &Player)>,
+    playfield: Res<Playfield>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (position, player) = player.single();
+
+    if hits_ground(position.0.y, player.body.circle.radius, &playfield) {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+/// Whether the player, with a body of this radius, would be on the ground at this height.
+pub fn hits_ground(y: f32, body_radius: f32, playfield: &Playfield) -> bool {
+    y < -playfield.height / 2.0 + 2.0 * body_radius // fine-tuned
+}
+
+pub fn hit_wall(
+    player: Query<&Position, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    walls: Query<&Wall>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    tuning: Res<GameTuning>,
+    masks: Res<PlayerMasks>,
+    animation: Res<FlapAnimation>,
+) {
+    let position = player.single();
+    let shape = PlayerShape::new(&tuning, &masks, *animation, power_ups.player_scale(&tuning));
+
+    for wall in walls.iter() {
+        if power_ups.shielded_hole == Some(wall.hole) {
+            continue;
+        }
+
+        if shape.hits(position.0, &wall.bounding_box) {
+            if power_ups.end(PowerUp::Shield) {
+                power_ups.shielded_hole = Some(wall.hole);
+            } else {
+                next_state.set(GameState::GameOver);
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::asset::io::Reader;
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+
+use crate::collision::PlayerCollision;
+use crate::difficulty::Difficulty;
+use crate::reachability::UnreachableHoles;
+
+// Gameplay numbers live in assets/tuning.ron. On desktop, the file is watched, and saving it changes the
+// game while it runs. Anything missing from the file keeps its default.
+
+pub const TUNING_PATH: &str = "tuning.ron";
+
+pub fn plugin(app: &mut App) {
+    app
+        .init_asset::<GameTuning>()
+        .register_asset_loader(GameTuningLoader)
+        .add_systems(Startup, load_tuning)
+        .add_systems(Update, apply_tuning.run_if(on_event::<AssetEvent<GameTuning>>()));
+}
+
+/// Units are world units and seconds.
+#[derive(Asset, TypePath, Resource, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GameTuning {
+    pub gravity: f32,
+    /// The upward speed the player is given by a flap.
+    pub impulse: f32,
+    pub wall_speed: f32,
+    /// Seconds between consecutive pairs of walls.
+    pub wall_interval: f32,
+    pub wall_width: f32,
+    pub tile_size: f32,
+    pub player_radius: f32,
+    pub player_collision: PlayerCollision,
+    /// Where the player's body and head are, relative to its position.
+    pub body_offset: Vec2,
+    pub head_offset: Vec2,
+    /// The size the player is taken to be when sizing holes, in player radii.
+    pub hole_scale: f32,
+    /// The smallest and largest holes start at these multiples of the player's size...
+    pub initial_min_hole: f32,
+    pub initial_max_hole: f32,
+    /// ...and approach this one, having gone halfway after this many walls.
+    pub final_hole: f32,
+    pub hole_shrink_walls: f32,
+    /// The furthest a hole can be from the previous one approaches the playfield height, having gone
+    /// halfway after this many walls.
+    pub hole_drift_walls: f32,
+    /// The closest a hole can be to the top or bottom of the playfield.
+    pub hole_margin: f32,
+    /// What's done with a hole the player can't get to in time from the previous one.
+    pub unreachable_holes: UnreachableHoles,
+    /// Walls may start moving vertically once this many holes have been generated: first oscillating up
+    /// and down, then opening and closing, then sliding in from the top or bottom.
+    pub oscillating_walls: u32,
+    pub opening_walls: u32,
+    pub sliding_walls: u32,
+    /// The chance that a pair of walls which could move does.
+    pub moving_wall_chance: f32,
+    /// Seconds per cycle of oscillating and opening walls...
+    pub wall_motion_period: f32,
+    /// ...and how far they move, as a fraction of the hole size.
+    pub wall_motion_amplitude: f32,
+    /// Seconds sliding walls take to slide into place.
+    pub wall_slide_time: f32,
+    /// The chance that a coin is placed in a hole, and that a power-up is placed after it.
+    pub coin_chance: f32,
+    pub power_up_chance: f32,
+    pub pickup_radius: f32,
+    /// Points added to the score for each coin and power-up collected.
+    pub coin_bonus: u64,
+    pub power_up_bonus: u64,
+    /// How long each power-up lasts, in seconds of simulated time.
+    pub shield_duration: f32,
+    pub slow_motion_duration: f32,
+    pub shrink_duration: f32,
+    /// How fast time passes in slow motion, and how large the player is when shrunk.
+    pub slow_motion_speed: f32,
+    pub shrink_scale: f32,
+}
+
+impl Default for GameTuning {
+    fn default() -> Self {
+        Self {
+            gravity: -819.2,
+            impulse: 384.0,
+            wall_speed: -256.0,
+            wall_interval: 1.5,
+            wall_width: 128.0,
+            tile_size: 128.0,
+            player_radius: 32.0,
+            player_collision: PlayerCollision::Mask,
+            body_offset: Vec2::new(27.0, -27.0),
+            head_offset: Vec2::new(47.0, 25.0),
+            hole_scale: 5.0,
+            initial_min_hole: 3.0,
+            initial_max_hole: 5.0,
+            final_hole: 1.1,
+            hole_shrink_walls: 10.0,
+            hole_drift_walls: 10.0,
+            hole_margin: 20.0,
+            unreachable_holes: UnreachableHoles::Move,
+            oscillating_walls: 10,
+            opening_walls: 20,
+            sliding_walls: 30,
+            moving_wall_chance: 0.5,
+            wall_motion_period: 2.0,
+            wall_motion_amplitude: 0.25,
+            wall_slide_time: 1.5,
+            coin_chance: 0.5,
+            power_up_chance: 0.1,
+            pickup_radius: 16.0,
+            coin_bonus: 1,
+            power_up_bonus: 5,
+            shield_duration: 8.0,
+            slow_motion_duration: 4.0,
+            shrink_duration: 6.0,
+            slow_motion_speed: 0.6,
+            shrink_scale: 0.6,
+        }
+    }
+}
+
+impl GameTuning {
+    /// Parses tuning in the format of the asset, e.g. to read it from disk when running without an asset server.
+    pub fn from_ron(text: &str) -> Result<Self, TuningError> {
+        ron::de::from_str(text).map_err(TuningError::Ron)
+    }
+
+    /// This tuning, made easier or harder by the difficulty's preset.
+    pub fn with_difficulty(&self, difficulty: Difficulty) -> Self {
+        let preset = difficulty.preset();
+
+        Self {
+            gravity: self.gravity * preset.gravity,
+            wall_speed: self.wall_speed * preset.wall_speed,
+            wall_interval: self.wall_interval * preset.wall_interval,
+            // scales every hole, whether generated or authored, as they're all multiples of the player's size
+            hole_scale: self.hole_scale * preset.hole_size,
+            hole_drift_walls: self.hole_drift_walls / preset.hole_drift,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TuningError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl Display for TuningError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TuningError::Io(err) => write!(f, "failed to read tuning: {}", err),
+            TuningError::Ron(err) => write!(f, "failed to parse tuning: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TuningError {}
+
+struct GameTuningLoader;
+
+impl AssetLoader for GameTuningLoader {
+    type Asset = GameTuning;
+    type Settings = ();
+    type Error = TuningError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut text = String::new();
+            reader.read_to_string(&mut text).await.map_err(TuningError::Io)?;
+            GameTuning::from_ron(&text)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// Keeps the tuning asset loaded, so that it's reloaded when the file changes.
+#[derive(Resource)]
+struct TuningHandle(Handle<GameTuning>);
+
+fn load_tuning(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(TuningHandle(asset_server.load(TUNING_PATH)));
+}
+
+fn apply_tuning(
+    mut events: EventReader<AssetEvent<GameTuning>>,
+    handle: Res<TuningHandle>,
+    assets: Res<Assets<GameTuning>>,
+    mut tuning: ResMut<GameTuning>,
+) {
+    for event in events.read() {
+        if event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0) {
+            if let Some(loaded) = assets.get(&handle.0) {
+                info!("applying {}", TUNING_PATH);
+                *tuning = loaded.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use bevy::input::ButtonState;
+use bevy::input::InputPlugin;
+use bevy::input::keyboard::{Key, KeyboardInput, NativeKey};
+use bevy::prelude::*;
+use bevy::window::ExitCondition;
+
+use flappy_bevy::{FlappyPlugin, FrameMask, GameState, headless, PauseState, Persistence, Player, PlayerMasks, Position, PreviousHole, Score, Seed, Tick, Velocity, Wall, WallSide, WallsCleared};
+
+// The whole game, menus and all, on MinimalPlugins, advanced one fixed tick per update and played with the
+// keyboard. Unless a test says otherwise, nothing is read from the assets directory, so it plays with the
+// default tuning and collides with circles, and nothing is persisted beyond the app.
+
+const SEED: u64 = 7;
+
+/// Enough ticks for any run which doesn't clear walls to have crashed.
+const MAX_TICKS: usize = 64 * 10;
+
+fn app() -> App {
+    app_with_assets("tests/no-assets")
+}
+
+fn app_with_assets(file_path: &str) -> App {
+    let mut app = App::new();
+
+    app
+        .add_plugins(MinimalPlugins)
+        .add_plugins(AssetPlugin {
+            file_path: file_path.into(),
+            watch_for_changes_override: Some(false),
+            ..default()
+        })
+        .add_plugins(InputPlugin)
+        .add_plugins(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            ..default()
+        })
+        .add_plugins(ImagePlugin::default())
+        // what the rendering plugins would otherwise set up
+        .init_asset::<TextureAtlasLayout>()
+        .init_asset::<Font>()
+        .add_plugins(FlappyPlugin {
+            seed: Some(SEED),
+            persistence: Persistence::Memory,
+            ..default()
+        });
+
+    // as App::run would, e.g. for ImagePlugin to register its loaders
+    app.finish();
+    app.cleanup();
+    headless::step_one_tick_per_update(&mut app);
+
+    // nothing is simulated until it's known what the player collides with, which is found out on another
+    // thread
+    for _ in 0..5000 {
+        app.update();
+        if !matches!(app.world.resource::<PlayerMasks>(), PlayerMasks::Loading) {
+            return app;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("bird.png neither loaded nor failed to");
+}
+
+fn state(app: &App) -> GameState {
+    app.world.resource::<State<GameState>>().get().clone()
+}
+
+fn go_to(app: &mut App, state: GameState) {
+    app.world.resource_mut::<NextState<GameState>>().set(state);
+    app.update();
+}
+
+fn start(app: &mut App) {
+    go_to(app, GameState::InProgress);
+    assert_eq!(state(app), GameState::InProgress);
+}
+
+fn tick(app: &App) -> u64 {
+    app.world.resource::<Tick>().0
+}
+
+fn walls_cleared(app: &App) -> u32 {
+    app.world.resource::<WallsCleared>().0
+}
+
+fn previous_hole(app: &App) -> u32 {
+    app.world.resource::<PreviousHole>().index
+}
+
+fn player(app: &mut App) -> (Vec2, Vec2) {
+    let (position, velocity) = app.world.query_filtered::<(&Position, &Velocity), With<Player>>().single(&app.world);
+    (position.0, velocity.0)
+}
+
+/// Each wall's side, and its bounds as (min, max).
+fn walls(app: &mut App) -> Vec<(WallSide, Vec2, Vec2)> {
+    app.world.query::<&Wall>().iter(&app.world)
+        .map(|wall| (wall.side, wall.center - wall.rectangle.half_size, wall.center + wall.rectangle.half_size))
+        .collect()
+}
+
+/// The middle of the next hole the player hasn't got past, if its walls have been spawned.
+fn next_hole(app: &mut App) -> Option<f32> {
+    let x = player(app).0.x;
+    let mut ahead: Vec<_> = walls(app).into_iter().filter(|(_, _, max)| max.x >= x).collect();
+    ahead.sort_by(|a, b| a.1.x.total_cmp(&b.1.x));
+
+    let top = ahead.iter().find(|(side, ..)| *side == WallSide::Top)?;
+    let bottom = ahead.iter().find(|(side, ..)| *side == WallSide::Bottom)?;
+    Some((top.1.y + bottom.2.y) / 2.0)
+}
+
+/// Presses and releases a key, as a window would report it, then updates.
+fn press(app: &mut App, key: KeyCode) {
+    for state in [ButtonState::Pressed, ButtonState::Released] {
+        app.world.send_event(KeyboardInput {
+            key_code: key,
+            logical_key: Key::Unidentified(NativeKey::Unidentified),
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+    }
+    app.update();
+}
+
+/// The label of the menu button with keyboard focus, if any.
+fn focused_button(app: &mut App) -> Option<String> {
+    let children = app.world.query_filtered::<(&BorderColor, &Children), With<Button>>().iter(&app.world)
+        .find(|(border, _)| border.0 == Color::BLACK)
+        .map(|(_, children)| children.to_vec())?;
+
+    children.iter().find_map(|child| app.world.get::<Text>(*child)).map(|text| text.sections[0].value.clone())
+}
+
+/// Moves the focus to the menu button labelled `label` with the arrow keys, and confirms it.
+fn choose(app: &mut App, label: &str) {
+    for _ in 0..20 {
+        if focused_button(app).as_deref() == Some(label) {
+            press(app, KeyCode::Enter);
+            // for whatever the button does to be handled, and any state it sets to be entered
+            app.update();
+            app.update();
+            return;
+        }
+        press(app, KeyCode::ArrowDown);
+    }
+    panic!("no button labelled {:?}", label);
+}
+
+fn texts(app: &mut App) -> Vec<String> {
+    app.world.query::<&Text>().iter(&app.world)
+        .map(|text| text.sections.iter().map(|section| section.value.as_str()).collect())
+        .collect()
+}
+
+/// Flaps whenever the player drops below `height`, so that it stays just above it, until the run ends.
+fn hover(app: &mut App, ticks: usize, height: impl Fn(&mut App) -> f32) {
+    for _ in 0..ticks {
+        if state(app) != GameState::InProgress {
+            return;
+        }
+
+        let target = height(app);
+        let (position, velocity) = player(app);
+        if position.y < target && velocity.y <= 0.0 {
+            press(app, KeyCode::Space);
+        } else {
+            app.update();
+        }
+    }
+}
+
+fn fly_through_holes(app: &mut App, ticks: usize) {
+    hover(app, ticks, |app| next_hole(app).unwrap_or(0.0) - 40.0);
+}
+
+fn crash(app: &mut App) {
+    for _ in 0..MAX_TICKS {
+        if state(app) == GameState::GameOver {
+            return;
+        }
+        app.update();
+    }
+    panic!("still flying after {} ticks", MAX_TICKS);
+}
+
+#[test]
+fn nothing_moves_in_the_menu() {
+    let mut app = app();
+    assert_eq!(state(&app), GameState::PreGame);
+
+    let before = player(&mut app);
+    for _ in 0..100 {
+        app.update();
+    }
+
+    assert_eq!(state(&app), GameState::PreGame);
+    assert_eq!(tick(&app), 0);
+    assert_eq!(player(&mut app), before);
+    assert!(walls(&mut app).is_empty());
+}
+
+#[test]
+fn nothing_is_simulated_until_its_known_what_the_player_collides_with() {
+    // without an asset server, so that nothing changes the masks but the test
+    let mut app = headless::app();
+    app.insert_resource(PlayerMasks::Loading);
+    start(&mut app);
+
+    let before = player(&mut app);
+    for _ in 0..10 {
+        app.update();
+    }
+    assert_eq!(tick(&app), 0);
+    assert_eq!(player(&mut app), before);
+
+    app.insert_resource(PlayerMasks::Unavailable);
+    app.update();
+    assert_eq!(tick(&app), 1);
+}
+
+#[test]
+fn without_flapping_the_player_falls_to_the_ground() {
+    let mut app = app();
+    start(&mut app);
+    let start_height = player(&mut app).0.y;
+
+    crash(&mut app);
+
+    let (position, _) = player(&mut app);
+    assert!(position.y < start_height);
+    assert_eq!(walls_cleared(&app), 0);
+    assert!(walls(&mut app).iter().all(|(_, min, _)| min.x > position.x), "the player reached a wall");
+
+    // nothing is simulated after the crash
+    let crashed_on = tick(&app);
+    for _ in 0..100 {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::GameOver);
+    assert_eq!(tick(&app), crashed_on);
+    assert_eq!(player(&mut app).0, position);
+}
+
+#[test]
+fn flapping_lifts_the_player() {
+    let mut app = app();
+    start(&mut app);
+    app.update();
+    let before = player(&mut app);
+
+    // the flap is read from the keyboard after the tick it was pressed on, and flown on the next
+    press(&mut app, KeyCode::Space);
+    app.update();
+
+    let after = player(&mut app);
+    assert!(before.1.y <= 0.0);
+    assert!(after.1.y > 0.0);
+    assert!(after.0.y > before.0.y);
+}
+
+#[test]
+fn walls_are_spawned_in_pairs_as_the_run_goes_on() {
+    let mut app = app();
+    start(&mut app);
+
+    let mut spawned = 0;
+    while spawned < 3 {
+        fly_through_holes(&mut app, 1);
+        assert_eq!(state(&app), GameState::InProgress, "crashed on tick {}", tick(&app));
+
+        let holes = previous_hole(&app);
+        assert!(holes == spawned || holes == spawned + 1, "holes skipped from {} to {}", spawned, holes);
+        spawned = holes;
+
+        let walls = walls(&mut app);
+        assert_eq!(walls.len() % 2, 0);
+        assert!(walls.len() as u32 <= 2 * spawned);
+        assert_eq!(walls.iter().filter(|(side, ..)| *side == WallSide::Top).count(), walls.len() / 2);
+    }
+
+    for (side, min, max) in walls(&mut app) {
+        match side {
+            WallSide::Top => assert!(max.y > min.y && min.y > -360.0),
+            WallSide::Bottom => assert!(max.y > min.y && max.y < 360.0),
+        }
+    }
+}
+
+#[test]
+fn flying_through_holes_clears_walls_and_scores() {
+    let mut app = app();
+    start(&mut app);
+
+    fly_through_holes(&mut app, 64 * 10);
+
+    assert_eq!(state(&app), GameState::InProgress, "crashed on tick {}", tick(&app));
+    assert!(walls_cleared(&app) >= 3, "only {} walls cleared", walls_cleared(&app));
+    assert!(previous_hole(&app) > walls_cleared(&app));
+
+    let score = app.world.resource::<Score>();
+    // pickups add to it
+    assert!(score.current >= score.stopwatch.elapsed().as_secs());
+    // simulated time, however long the updates took
+    assert_eq!(score.stopwatch.elapsed().as_secs(), tick(&app) / 64);
+    assert!(score.stopwatch.elapsed().as_secs() >= 9);
+    assert_eq!(score.high, score.current);
+}
+
+#[test]
+fn flying_into_a_wall_ends_the_run() {
+    let mut app = app();
+    start(&mut app);
+
+    // well away from the hole, on whichever side there's more room
+    hover(&mut app, MAX_TICKS, |app| match next_hole(app) {
+        Some(hole) if hole > 0.0 => hole - 250.0,
+        Some(hole) => hole + 150.0,
+        None => 0.0,
+    });
+
+    assert_eq!(state(&app), GameState::GameOver);
+    assert_eq!(walls_cleared(&app), 0);
+
+    let (left, right) = {
+        let player = app.world.query::<&Player>().single(&app.world);
+        let (head, body) = (player.head, player.body);
+        ((head.center.x - head.radius()).min(body.center.x - body.radius()), (head.center.x + head.radius()).max(body.center.x + body.radius()))
+    };
+    let alongside = walls(&mut app).into_iter().any(|(_, min, max)| min.x <= right && max.x >= left);
+    assert!(alongside, "crashed away from the walls, between {} and {}", left, right);
+}
+
+#[test]
+fn restarting_starts_the_same_course_over() {
+    let mut app = app();
+    start(&mut app);
+    fly_through_holes(&mut app, 64 * 5);
+    let ticks = tick(&app);
+    let first_run = (walls(&mut app), walls_cleared(&app), previous_hole(&app));
+    let start_position = {
+        let mut fresh = self::app();
+        start(&mut fresh);
+        player(&mut fresh).0
+    };
+
+    crash(&mut app);
+    let high = app.world.resource::<Score>().high;
+    assert!(high >= 5);
+
+    start(&mut app);
+    assert_eq!(tick(&app), 0);
+    assert_eq!(walls_cleared(&app), 0);
+    assert_eq!(previous_hole(&app), 0);
+    assert!(walls(&mut app).is_empty());
+    assert_eq!(player(&mut app).0, start_position);
+    assert_eq!(app.world.resource::<Score>().current, 0);
+    assert_eq!(app.world.resource::<Score>().high, high);
+
+    // the same seed and the same flaps fly the same course
+    while tick(&app) < ticks {
+        fly_through_holes(&mut app, 1);
+    }
+    assert_eq!((walls(&mut app), walls_cleared(&app), previous_hole(&app)), first_run);
+}
+
+#[test]
+fn going_back_to_the_menu_clears_the_course() {
+    let mut app = app();
+    start(&mut app);
+    fly_through_holes(&mut app, 64 * 3);
+    crash(&mut app);
+    assert!(!walls(&mut app).is_empty());
+
+    go_to(&mut app, GameState::PreGame);
+
+    assert_eq!(state(&app), GameState::PreGame);
+    assert!(walls(&mut app).is_empty());
+    assert_eq!(app.world.resource::<Score>().stopwatch.elapsed().as_secs(), 0);
+
+    start(&mut app);
+    assert_eq!(tick(&app), 0);
+    assert_eq!(previous_hole(&app), 0);
+}
+
+#[test]
+fn restarting_from_the_pause_menu_starts_the_run_over() {
+    let mut app = app();
+    start(&mut app);
+    fly_through_holes(&mut app, 64 * 5);
+    let ticks = tick(&app);
+    assert!(previous_hole(&app) > 0);
+
+    press(&mut app, KeyCode::Escape);
+    app.update();
+    choose(&mut app, "Restart");
+    while state(&app) != GameState::InProgress {
+        app.update();
+    }
+
+    assert!(tick(&app) < ticks);
+    assert_eq!(previous_hole(&app), 0);
+    assert!(walls(&mut app).is_empty());
+    assert_eq!(*app.world.resource::<State<PauseState>>().get(), PauseState::Running);
+    assert!(texts(&mut app).iter().all(|text| text != "Paused"));
+
+    let before = tick(&app);
+    app.update();
+    assert_eq!(tick(&app), before + 1);
+}
+
+#[test]
+fn a_run_quit_from_the_pause_menu_isnt_put_on_the_leaderboard() {
+    let mut app = app();
+    start(&mut app);
+    fly_through_holes(&mut app, 64 * 5);
+    assert!(app.world.resource::<Score>().current > 0);
+
+    press(&mut app, KeyCode::Escape);
+    app.update();
+    choose(&mut app, "Quit to Menu");
+    assert_eq!(state(&app), GameState::PreGame);
+
+    // a run which scores nothing doesn't make the leaderboard, and isn't taken for the quit one
+    start(&mut app);
+    crash(&mut app);
+    app.update();
+    assert_eq!(app.world.resource::<Score>().current, 0);
+    assert!(texts(&mut app).iter().all(|text| !text.contains("New #")), "asked to name a run: {:?}", texts(&mut app));
+}
+
+#[test]
+fn bird_png_is_loaded_to_collide_with_as_headless_runs_read_it() {
+    let mut app = app_with_assets("assets");
+
+    let PlayerMasks::Loaded(loaded) = app.world.resource::<PlayerMasks>() else {
+        panic!("bird.png wasn't made into masks");
+    };
+    let read = headless::read_player_masks();
+    let corners = |mask: &FrameMask| {
+        let bounds = mask.bounds(1.0);
+        (bounds.min, bounds.max)
+    };
+    assert_eq!(loaded.len(), 8);
+    for (frame, mask) in loaded.iter().enumerate() {
+        assert_eq!(Some(corners(mask)), read.frame(frame).map(corners));
+    }
+
+    // holes are placed for the player's shape, so the same assets make the same course
+    let mut headless = headless::app();
+    headless::load_assets(&mut headless);
+    headless.world.resource_mut::<Seed>().0 = SEED;
+
+    for app in [&mut app, &mut headless] {
+        start(app);
+        crash(app);
+    }
+    assert_eq!(tick(&app), tick(&headless));
+    assert_eq!(walls(&mut app), walls(&mut headless));
+}